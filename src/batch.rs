@@ -0,0 +1,185 @@
+use monet::driver::Outcome;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Extensions of the image files picked up from a directory.
+const IMAGE_EXTENSIONS: &'static [&'static str] = &[
+    "png",
+    "jpg",
+    "jpeg",
+    "gif",
+    "bmp",
+    "ico",
+    "tif",
+    "tiff",
+    "webp",
+    "ppm",
+    "pnm",
+    "tga",
+];
+
+/// The images to paint for a batch given on the command line: every image file in a
+/// directory, or the files matching a glob such as `photos/*.jpg`, where `*` matches any
+/// characters and `?` a single one. Only the file name may contain wildcards.
+pub fn find_images(spec: &str) -> io::Result<Vec<PathBuf>> {
+    let path = Path::new(spec);
+    let (directory, pattern) = if path.is_dir() {
+        (path.to_path_buf(), None)
+    } else {
+        let pattern = path.file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid batch glob"))?;
+        let directory = match path.parent() {
+            Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        (directory, Some(pattern.to_string()))
+    };
+    let mut images = Vec::new();
+    for entry in fs::read_dir(&directory)? {
+        let path = entry?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let wanted = match pattern {
+            Some(ref pattern) => matches(pattern, &name),
+            None => is_image(&path),
+        };
+        if wanted && path.is_file() {
+            images.push(path);
+        }
+    }
+    images.sort();
+    return Ok(images);
+}
+
+/// Where to save a file of the given kind, such as `genome.txt`, for one image of a batch:
+/// next to the painting, prefixed with the image's file name. The image's extension is kept,
+/// so `a.jpg` and `a.png` don't overwrite each other's files.
+pub fn output_path(directory: &Path, image: &Path, file: &str) -> PathBuf {
+    let file = Path::new(file)
+        .file_name()
+        .and_then(|file| file.to_str())
+        .unwrap_or(file);
+    return directory.join(format!("{}-{}", image_name(image), file));
+}
+
+/// Where to save the painting of one image of a batch, such as `a.jpg.png` for `a.jpg`.
+pub fn painting_path(directory: &Path, image: &Path, extension: &str) -> PathBuf {
+    return directory.join(format!("{}.{}", image_name(image), extension));
+}
+
+/// Writes a summary of a batch painted into `directory`, one line per image, as a CSV file.
+pub fn save_report(
+    path: &Path,
+    directory: &Path,
+    extension: &str,
+    outcomes: &[Outcome],
+) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(file, "image,output,fitness,seconds,error")?;
+    for outcome in outcomes {
+        let (fitness, error) = match outcome.fitness {
+            Ok(fitness) => (fitness.to_string(), String::new()),
+            Err(ref error) => (String::new(), error.to_string().replace('"', "'")),
+        };
+        writeln!(
+            file,
+            "\"{}\",\"{}\",{},{:.1},\"{}\"",
+            outcome.image.display(),
+            painting_path(directory, &outcome.image, extension).display(),
+            fitness,
+            outcome.seconds,
+            error
+        )?;
+    }
+    return Ok(());
+}
+
+/// The file name of an image, including its extension.
+fn image_name(image: &Path) -> String {
+    return image.file_name()
+        .map_or("painting".to_string(), |name| name.to_string_lossy().into_owned());
+}
+
+/// Whether the file has the extension of an image.
+fn is_image(path: &Path) -> bool {
+    return path.extension()
+        .and_then(|extension| extension.to_str())
+        .map_or(false, |extension| {
+            IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+        });
+}
+
+/// Whether a file name matches a pattern with `*` and `?` wildcards.
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Where the last `*` was seen, and how much of the name it has swallowed.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the last `*` swallow one more character and try again.
+            star = Some((star_p, star_n + 1));
+            p = star_p + 1;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+    return pattern[p..].iter().all(|&c| c == '*');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+    use std::path::Path;
+
+    #[test]
+    fn star_matches_any_characters() {
+        assert!(matches("*", "photo.jpg"));
+        assert!(matches("*", ""));
+        assert!(matches("*.jpg", "photo.jpg"));
+        assert!(!matches("*.jpg", "photo.png"));
+        assert!(matches("photo*", "photo"));
+    }
+
+    #[test]
+    fn several_stars_backtrack() {
+        assert!(matches("a*b*c", "abc"));
+        assert!(matches("a*b*c", "axxbyybc"));
+        assert!(matches("a*b*c", "abcbc"));
+        assert!(!matches("a*b*c", "acb"));
+        assert!(!matches("a*b*c", "abcd"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(matches("frame?.png", "frame1.png"));
+        assert!(!matches("frame?.png", "frame.png"));
+        assert!(!matches("frame?.png", "frame12.png"));
+        assert!(matches("??", "ab"));
+    }
+
+    #[test]
+    fn outputs_keep_the_image_extension() {
+        let directory = Path::new("out");
+        assert_eq!(
+            super::painting_path(directory, Path::new("in/a.jpg"), "png"),
+            Path::new("out/a.jpg.png")
+        );
+        assert_ne!(
+            super::output_path(directory, Path::new("a.jpg"), "genome.txt"),
+            super::output_path(directory, Path::new("a.png"), "genome.txt")
+        );
+    }
+}
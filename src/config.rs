@@ -0,0 +1,346 @@
+use driver::Selection;
+use std::sync::Arc;
+use stroke::background::Background;
+use stroke::brush::Brush;
+use stroke::color::ColorSampling;
+use stroke::color_palette::ColorPalette;
+use stroke::detail::Placement;
+use stroke::error::{MonetError, Result};
+use stroke::lineart::LineArt;
+use stroke::mask::Mask;
+use stroke::mixing::Mixing;
+use stroke::postprocess::PostProcess;
+use stroke::primitive::PrimitiveKind;
+use stroke::width_profile::ProfileShape;
+
+/// How the paintings of the initial population are generated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Generator {
+    /// Strokes anywhere, colored like the pixel they start on.
+    Informed,
+    /// Strokes following the edges and contours of the image.
+    Gradient,
+    /// Strokes anywhere, colored like a random pixel of the image.
+    Random,
+    /// Round dots gathering in detailed areas.
+    Pointillist,
+}
+
+impl Generator {
+    /// Parses the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Generator> {
+        return match name {
+            "informed" => Some(Generator::Informed),
+            "gradient" => Some(Generator::Gradient),
+            "random" => Some(Generator::Random),
+            "pointillist" => Some(Generator::Pointillist),
+            _ => None,
+        };
+    }
+
+    /// The name used on the command line.
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            Generator::Informed => "informed",
+            Generator::Gradient => "gradient",
+            Generator::Random => "random",
+            Generator::Pointillist => "pointillist",
+        };
+    }
+}
+
+/// Everything about how one painting is generated and judged: the geometry and look of its
+/// strokes, their colors, and what the fitness compares.
+#[derive(Clone)]
+pub struct PaintingConfig {
+    pub generator: Generator,
+    /// The number of strokes, or of dots for pointillist paintings.
+    pub strokes: u32,
+    /// The largest width of a stroke, or the largest radius of a dot.
+    pub stroke_width: u32,
+    pub min_length: u32,
+    pub max_length: u32,
+    pub color_sampling: ColorSampling,
+    pub placement: Placement,
+    /// Weights the error of each pixel in the fitness.
+    pub mask: Option<Arc<Mask>>,
+    /// Whether strokes only start on pixels the mask covers.
+    pub restrict_to_mask: bool,
+    pub background: Background,
+    /// Strokes pick one of these at random.
+    pub brushes: Vec<Brush>,
+    pub profile_shape: ProfileShape,
+    /// Strokes are replaced by shapes of about the same size if these pick another kind.
+    pub kinds: Vec<PrimitiveKind>,
+    /// Restricts the painting to these colors.
+    pub palette: Option<ColorPalette>,
+    /// Colors strokes start from, the closest to the color they sample. Ignored by the
+    /// random generator.
+    pub dominant_colors: Option<ColorPalette>,
+    pub mixing: Mixing,
+    pub line_art: Option<LineArt>,
+    pub grayscale: bool,
+}
+
+impl Default for PaintingConfig {
+    fn default() -> PaintingConfig {
+        return PaintingConfig {
+            generator: Generator::Informed,
+            strokes: 500,
+            stroke_width: 5,
+            min_length: 5,
+            max_length: 150,
+            color_sampling: ColorSampling::Pixel,
+            placement: Placement::Uniform,
+            mask: None,
+            restrict_to_mask: false,
+            background: Background::default(),
+            brushes: vec![Brush::Flat],
+            profile_shape: ProfileShape::Constant,
+            kinds: vec![PrimitiveKind::Stroke],
+            palette: None,
+            dominant_colors: None,
+            mixing: Mixing::Alpha,
+            line_art: None,
+            grayscale: false,
+        };
+    }
+}
+
+impl PaintingConfig {
+    /// Starts from the defaults, which the command line uses too, though it has no default
+    /// number of strokes.
+    pub fn builder() -> PaintingConfigBuilder {
+        return PaintingConfigBuilder { config: PaintingConfig::default() };
+    }
+
+    /// Checks the settings make sense together. The generators check them too, as the fields
+    /// can be set without the builder.
+    pub fn check(&self) -> Result<()> {
+        if self.strokes == 0 {
+            return Err(MonetError::Invalid("a painting needs at least 1 stroke".to_string()));
+        }
+        if self.stroke_width == 0 {
+            return Err(MonetError::Invalid("the stroke width has to be at least 1".to_string()));
+        }
+        if self.min_length >= self.max_length {
+            return Err(MonetError::Invalid(format!(
+                "the minimum stroke length {} has to be below the maximum {}",
+                self.min_length,
+                self.max_length
+            )));
+        }
+        if self.line_art.map_or(false, |line_art| line_art.width == 0) {
+            return Err(MonetError::Invalid("the line width has to be at least 1".to_string()));
+        }
+        if self.brushes.is_empty() || self.kinds.is_empty() {
+            return Err(MonetError::Invalid(
+                "a painting needs at least one brush and kind of primitive".to_string(),
+            ));
+        }
+        return Ok(());
+    }
+}
+
+/// Builds a `PaintingConfig`. Every method sets the setting of the same name.
+pub struct PaintingConfigBuilder {
+    config: PaintingConfig,
+}
+
+impl PaintingConfigBuilder {
+    pub fn generator(mut self, generator: Generator) -> PaintingConfigBuilder {
+        self.config.generator = generator;
+        return self;
+    }
+
+    pub fn strokes(mut self, strokes: u32) -> PaintingConfigBuilder {
+        self.config.strokes = strokes;
+        return self;
+    }
+
+    pub fn stroke_width(mut self, stroke_width: u32) -> PaintingConfigBuilder {
+        self.config.stroke_width = stroke_width;
+        return self;
+    }
+
+    /// Sets the minimum and maximum stroke length.
+    pub fn length(mut self, min_length: u32, max_length: u32) -> PaintingConfigBuilder {
+        self.config.min_length = min_length;
+        self.config.max_length = max_length;
+        return self;
+    }
+
+    pub fn color_sampling(mut self, color_sampling: ColorSampling) -> PaintingConfigBuilder {
+        self.config.color_sampling = color_sampling;
+        return self;
+    }
+
+    pub fn placement(mut self, placement: Placement) -> PaintingConfigBuilder {
+        self.config.placement = placement;
+        return self;
+    }
+
+    /// Sets the mask, and whether strokes only start on pixels it covers.
+    pub fn mask(mut self, mask: Option<Arc<Mask>>, restrict: bool) -> PaintingConfigBuilder {
+        self.config.mask = mask;
+        self.config.restrict_to_mask = restrict;
+        return self;
+    }
+
+    pub fn background(mut self, background: Background) -> PaintingConfigBuilder {
+        self.config.background = background;
+        return self;
+    }
+
+    pub fn brushes(mut self, brushes: Vec<Brush>) -> PaintingConfigBuilder {
+        self.config.brushes = brushes;
+        return self;
+    }
+
+    pub fn profile_shape(mut self, profile_shape: ProfileShape) -> PaintingConfigBuilder {
+        self.config.profile_shape = profile_shape;
+        return self;
+    }
+
+    pub fn kinds(mut self, kinds: Vec<PrimitiveKind>) -> PaintingConfigBuilder {
+        self.config.kinds = kinds;
+        return self;
+    }
+
+    pub fn palette(mut self, palette: Option<ColorPalette>) -> PaintingConfigBuilder {
+        self.config.palette = palette;
+        return self;
+    }
+
+    pub fn dominant_colors(mut self, colors: Option<ColorPalette>) -> PaintingConfigBuilder {
+        self.config.dominant_colors = colors;
+        return self;
+    }
+
+    pub fn mixing(mut self, mixing: Mixing) -> PaintingConfigBuilder {
+        self.config.mixing = mixing;
+        return self;
+    }
+
+    pub fn line_art(mut self, line_art: Option<LineArt>) -> PaintingConfigBuilder {
+        self.config.line_art = line_art;
+        return self;
+    }
+
+    pub fn grayscale(mut self, grayscale: bool) -> PaintingConfigBuilder {
+        self.config.grayscale = grayscale;
+        return self;
+    }
+
+    /// Checks the settings and returns the configuration.
+    pub fn build(self) -> Result<PaintingConfig> {
+        self.config.check()?;
+        return Ok(self.config);
+    }
+}
+
+/// Where and how the most fit painting of a run is saved.
+#[derive(Clone)]
+pub struct Output {
+    /// The image of the painting, in the format given by its extension.
+    pub image: String,
+    pub postprocess: PostProcess,
+    /// The text file the primitives of the painting are saved to, if any.
+    pub genome: Option<String>,
+    /// The file strokes are saved to for a pen plotter, if any.
+    pub plot: Option<String>,
+}
+
+impl Default for Output {
+    fn default() -> Output {
+        return Output {
+            image: "painting.png".to_string(),
+            postprocess: PostProcess::default(),
+            genome: None,
+            plot: None,
+        };
+    }
+}
+
+/// Everything about one run of the genetic algorithm: how many paintings evolve for how
+/// long, how they are selected, what they look like, and where the result is saved.
+#[derive(Clone)]
+pub struct RunConfig {
+    pub population: u32,
+    pub iterations: u64,
+    pub selection: Selection,
+    /// Whether the most fit painting is recolored after evolving.
+    pub recolor: bool,
+    pub painting: PaintingConfig,
+    pub output: Output,
+}
+
+impl Default for RunConfig {
+    fn default() -> RunConfig {
+        return RunConfig {
+            population: 100,
+            iterations: 100,
+            selection: Selection::Stochastic,
+            recolor: false,
+            painting: PaintingConfig::default(),
+            output: Output::default(),
+        };
+    }
+}
+
+impl RunConfig {
+    /// Starts from the defaults, which the command line uses too, though it has no default
+    /// population.
+    pub fn builder() -> RunConfigBuilder {
+        return RunConfigBuilder { config: RunConfig::default() };
+    }
+}
+
+/// Builds a `RunConfig`. Every method sets the setting of the same name.
+pub struct RunConfigBuilder {
+    config: RunConfig,
+}
+
+impl RunConfigBuilder {
+    pub fn population(mut self, population: u32) -> RunConfigBuilder {
+        self.config.population = population;
+        return self;
+    }
+
+    pub fn iterations(mut self, iterations: u64) -> RunConfigBuilder {
+        self.config.iterations = iterations;
+        return self;
+    }
+
+    pub fn selection(mut self, selection: Selection) -> RunConfigBuilder {
+        self.config.selection = selection;
+        return self;
+    }
+
+    pub fn recolor(mut self, recolor: bool) -> RunConfigBuilder {
+        self.config.recolor = recolor;
+        return self;
+    }
+
+    pub fn painting(mut self, painting: PaintingConfig) -> RunConfigBuilder {
+        self.config.painting = painting;
+        return self;
+    }
+
+    pub fn output(mut self, output: Output) -> RunConfigBuilder {
+        self.config.output = output;
+        return self;
+    }
+
+    /// Checks the settings and returns the configuration.
+    pub fn build(self) -> Result<RunConfig> {
+        // Two paintings at least, so there is something to breed.
+        if self.config.population < 2 {
+            return Err(MonetError::Invalid(
+                "the population needs at least 2 paintings".to_string(),
+            ));
+        }
+        self.config.painting.check()?;
+        return Ok(self.config);
+    }
+}
@@ -0,0 +1,215 @@
+use rsgenetic::pheno::Phenotype;
+use rsgenetic::sim::par::Simulator;
+use rsgenetic::sim::*;
+use rsgenetic::sim::select::*;
+use std::panic;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+use config::{Output, RunConfig};
+use stroke::{Painting, Target};
+use stroke::background::Background;
+use stroke::error::{MonetError, Result};
+
+/// How the paintings that breed the next generation are picked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selection {
+    /// Picks parents at random, favouring fitter paintings.
+    Stochastic,
+    /// Breeds the fittest paintings.
+    Maximize,
+    /// Breeds the winners of tournaments between random paintings.
+    Tournament,
+    /// Breeds the fittest paintings, sorting the population in parallel.
+    ParMaximize,
+}
+
+impl Selection {
+    /// Parses the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Selection> {
+        return match name {
+            "stochastic" => Some(Selection::Stochastic),
+            "maximize" => Some(Selection::Maximize),
+            "tournament" => Some(Selection::Tournament),
+            "parmaximize" => Some(Selection::ParMaximize),
+            _ => None,
+        };
+    }
+
+    /// The name used on the command line.
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            Selection::Stochastic => "stochastic",
+            Selection::Maximize => "maximize",
+            Selection::Tournament => "tournament",
+            Selection::ParMaximize => "parmaximize",
+        };
+    }
+}
+
+/// Generates the initial population of a run.
+pub fn populate(target: &Target, config: &RunConfig) -> Result<Vec<Painting>> {
+    return (0..config.population)
+        .map(|_| Painting::generate(target, &config.painting))
+        .collect();
+}
+
+/// Starts a population of `size` paintings of `target` from `seed`, the most fit painting of
+/// the previous frame of an animation: the seed itself, and mutations of it, on the frame's
+/// own `background`.
+pub fn follow(
+    seed: &Painting,
+    target: &Target,
+    coherence: f64,
+    background: Background,
+    size: u32,
+) -> Result<Vec<Painting>> {
+    let first = seed.follow(target, coherence, background)?;
+    return Ok(
+        (0..size)
+            .map(|i| if i == 0 { first.clone() } else { first.mutate() })
+            .collect(),
+    );
+}
+
+/// Runs the genetic algorithm on a population for `iterations` generations, and returns the
+/// most fit painting.
+pub fn evolve(
+    population: &mut Vec<Painting>,
+    selection: Selection,
+    iterations: u64,
+) -> Result<Painting> {
+    let size = population.len();
+    let s = Simulator::builder(population).set_max_iters(iterations);
+
+    // TODO figure out proper parameters and how tournament works
+    let mut simulator = match selection {
+        Selection::Stochastic => {
+            s.set_selector(Box::new(StochasticSelector::new(10)))
+                .build()
+        }
+        Selection::Maximize => s.set_selector(Box::new(MaximizeSelector::new(10))).build(),
+        Selection::Tournament => {
+            s.set_selector(Box::new(TournamentSelector::new(size / 4, size / 3 + (size / 3) % 2)))
+                .build()
+        }
+        Selection::ParMaximize => {
+            s.set_selector(Box::new(ParMaximizeSelector::new(size / 3 + size % 2)))
+                .build()
+        }
+    };
+
+    simulator.run();
+    let most_fit = simulator
+        .get()
+        .map_err(|error| MonetError::Paint(format!("painting failed: {:?}", error)))?
+        .clone();
+    return Ok(most_fit);
+}
+
+/// Evolves a population as `config` says, and returns the most fit painting, recolored if
+/// asked for.
+pub fn run(population: &mut Vec<Painting>, config: &RunConfig) -> Result<Painting> {
+    let mut most_fit = evolve(population, config.selection, config.iterations)?;
+    if config.recolor {
+        most_fit.recolor();
+    }
+    return Ok(most_fit);
+}
+
+/// Saves a painting, along with its plot and genome if asked for.
+pub fn save(painting: &Painting, output: &Output) -> Result<()> {
+    painting.render_and_save_image(output.image.clone(), &output.postprocess)?;
+    if let Some(ref plot_file) = output.plot {
+        painting.save_plot(plot_file)?;
+    }
+    if let Some(ref genome_file) = output.genome {
+        painting.save_genome(genome_file)?;
+    }
+    return Ok(());
+}
+
+/// Paints one target as `config` says: generates the population, evolves it and saves the
+/// most fit painting, which is returned.
+pub fn paint(target: &Target, config: &RunConfig) -> Result<Painting> {
+    let mut population = populate(target, config)?;
+    let most_fit = run(&mut population, config)?;
+    save(&most_fit, &config.output)?;
+    return Ok(most_fit);
+}
+
+/// How painting one image of a batch went.
+pub struct Outcome {
+    pub image: PathBuf,
+    /// The fitness of the saved painting, or why it could not be painted.
+    pub fitness: Result<i32>,
+    pub seconds: f64,
+}
+
+/// Paints every image of a batch, `jobs` at a time, each with the run `configure` gives for
+/// its target. An image that fails to paint doesn't stop the others. The outcomes are in the
+/// order of `images`.
+pub fn paint_batch<F>(images: &[PathBuf], jobs: usize, configure: F) -> Vec<Outcome>
+where
+    F: Fn(&Target) -> Result<RunConfig> + Send + Sync + 'static,
+{
+    let configure = Arc::new(configure);
+    let queue = Arc::new(Mutex::new(images.iter().cloned().rev().collect::<Vec<_>>()));
+    let outcomes = Arc::new(Mutex::new(Vec::new()));
+    let workers: Vec<_> = (0..jobs.max(1))
+        .map(|_| {
+            let (configure, queue, outcomes) = (configure.clone(), queue.clone(), outcomes.clone());
+            thread::spawn(move || loop {
+                let image = match queue.lock().unwrap().pop() {
+                    Some(image) => image,
+                    None => break,
+                };
+                let started = Instant::now();
+                let fitness = Target::load(&image.to_string_lossy())
+                    .and_then(|target| paint(&target, &configure(&target)?))
+                    .map(|painting| painting.fitness());
+                let elapsed = started.elapsed();
+                outcomes.lock().unwrap().push(Outcome {
+                    image: image,
+                    fitness: fitness,
+                    seconds: elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9,
+                });
+            })
+        })
+        .collect();
+    for worker in workers {
+        if let Err(panic) = worker.join() {
+            panic::resume_unwind(panic);
+        }
+    }
+
+    let mut outcomes: Vec<Outcome> = outcomes.lock().unwrap().drain(..).collect();
+    outcomes.sort_by_key(|outcome| images.iter().position(|image| *image == outcome.image));
+    return outcomes;
+}
+
+/// Paints the frames of an animation in order, each with the run `configure` gives for its
+/// index and target. Every frame but the first starts from the most fit painting of the
+/// frame before, as `follow` does, so primitives persist and move smoothly between frames.
+pub fn paint_sequence<F>(frames: &[PathBuf], coherence: f64, mut configure: F) -> Result<()>
+where
+    F: FnMut(usize, &Target) -> Result<RunConfig>,
+{
+    let mut previous: Option<Painting> = None;
+    for (index, frame) in frames.iter().enumerate() {
+        let target = Target::load(&frame.to_string_lossy())?;
+        let config = configure(index, &target)?;
+        let mut population = match previous {
+            Some(ref seed) => {
+                let background = config.painting.background.clone();
+                follow(seed, &target, coherence, background, config.population)?
+            }
+            None => populate(&target, &config)?,
+        };
+        let most_fit = run(&mut population, &config)?;
+        save(&most_fit, &config.output)?;
+        previous = Some(most_fit);
+    }
+    return Ok(());
+}
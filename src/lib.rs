@@ -0,0 +1,16 @@
+//! Paints pictures with a genetic algorithm: populations of paintings made of strokes and
+//! other primitives are evolved to look as much as possible like a target image.
+extern crate palette;
+extern crate imageproc;
+extern crate rsgenetic;
+extern crate image;
+extern crate rand;
+pub mod stroke;
+pub mod driver;
+pub mod config;
+pub use stroke::{Painting, Stroke, Target};
+pub use stroke::point_2d::Point2D;
+pub use stroke::primitive::Primitive;
+pub use stroke::error::{MonetError, Result};
+pub use driver::{Selection, evolve};
+pub use config::{Output, PaintingConfig, RunConfig};
@@ -1,18 +1,54 @@
-extern crate palette;
-extern crate imageproc;
-extern crate rsgenetic;
-extern crate image;
 extern crate clap;
-extern crate rand;
-mod stroke;
-use stroke::Painting;
-use rsgenetic::sim::par::Simulator;
-use rsgenetic::sim::*;
-use rsgenetic::sim::select::*;
-use clap::{Arg, App};
+extern crate monet;
+extern crate toml;
+mod batch;
+mod style;
+use monet::{Output, PaintingConfig, RunConfig, Selection, Target, driver};
+use monet::config::Generator;
+use monet::stroke::error::{MonetError, Result};
+use monet::stroke::mask::Mask;
+use monet::stroke::color::ColorSampling;
+use monet::stroke::detail::Placement;
+use monet::stroke::background::Background;
+use monet::stroke::brush::Brush;
+use monet::stroke::width_profile::ProfileShape;
+use monet::stroke::primitive::PrimitiveKind;
+use monet::stroke::color_palette::ColorPalette;
+use monet::stroke::color::format_color;
+use monet::stroke::quantize::{ColorSpace, Quantizer};
+use monet::stroke::mixing::Mixing;
+use monet::stroke::postprocess::{PostProcess, Texture};
+use monet::stroke::lineart::{LineArt, LineTarget};
+use monet::stroke::format;
+use style::Style;
+use clap::{Arg, App, ArgMatches};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
 
 
+/// Every option a config file can set, by its long name, in the order `--dump-config` prints
+/// them.
+const OPTIONS: &'static [&'static str] = &[
+    "population", "strokes", "file", "batch", "sequence", "coherence", "output", "output-dir",
+    "format", "samples", "jobs", "iterations", "verbose", "generator",
+    "colorsampling", "recolor", "placement", "mask", "maskplacement", "background", "brush",
+    "widthprofile", "primitives", "genome", "palette", "dominantcolors", "quantizer",
+    "quantizespace", "exportpalette", "mixing", "texture", "texturestrength", "impasto", "jitter",
+    "grayscale", "lineart", "linewidth", "plot", "style", "selector", "strokewidth",
+    "minstrokelength", "maxstrokelength",
+];
+
 fn main() {
+    if let Err(error) = run() {
+        eprintln!("error: {}", error);
+        process::exit(error.exit_code());
+    }
+}
+
+/// Paints what the command line asks for.
+fn run() -> Result<()> {
     let args = App::new("Genetic Painter")
         .version("1.0")
         .author("Alexander Hansen <alex@alex-hansen.com>")
@@ -22,27 +58,140 @@ fn main() {
                 .short("n")
                 .long("population")
                 .value_name("POPSIZE")
-                .help("Sets the size of the initial population")
-                .takes_value(true)
-                .required(true),
+                .help(
+                    "Sets the size of the initial population. Required unless given by a \
+                                   config file.",
+                )
+                .takes_value(true),
         )
         .arg(
             Arg::with_name("strokes")
                 .short("s")
                 .long("strokes")
                 .value_name("NUMSTROKES")
-                .help("Sets the number of strokes per painting in the population")
-                .takes_value(true)
-                .required(true),
+                .help(
+                    "Sets the number of strokes per painting in the population. Required \
+                                   unless given by a config file or the style.",
+                )
+                .takes_value(true),
         )
         .arg(
-            Arg::with_name("imagefile")
+            Arg::with_name("file")
                 .short("f")
                 .long("file")
                 .value_name("IMAGEFILE")
-                .help("Selects the image file to use for painting")
+                .help(
+                    "Selects the image file to use for painting. Required unless a batch or \
+                                   sequence is painted, or given by a config file.",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("FILE")
+                .takes_value(true)
+                .help(
+                    "Reads options from a TOML file, one `option = value` per line by the \
+                                   long name of the option, such as `strokes = 600` or \
+                                   `brush = \"bristle\"`. Options given on the command line \
+                                   win over the file's, and the file's over the style's.",
+                ),
+        )
+        .arg(
+            Arg::with_name("dump-config")
+                .long("dump-config")
+                .help(
+                    "Prints the options in effect as a config file for --config, and exits \
+                                   without painting.",
+                ),
+        )
+        .arg(
+            Arg::with_name("batch")
+                .long("batch")
+                .value_name("IMAGES")
+                .takes_value(true)
+                .conflicts_with_all(&["file", "sequence"])
+                .help(
+                    "Paints every image in a directory, or every file matching a glob such \
+                                   as photos/*.jpg, instead of a single image. Needs \
+                                   --output-dir, where photo.jpg is saved as photo.jpg.png.",
+                ),
+        )
+        .arg(
+            Arg::with_name("sequence")
+                .long("sequence")
+                .value_name("FRAMES")
                 .takes_value(true)
-                .required(true),
+                .conflicts_with("file")
+                .help(
+                    "Paints the frames of an animation, in the order of their file names, \
+                                   from a directory or a glob such as frames/*.png. Every frame \
+                                   starts from the painting of the one before. Needs \
+                                   --output-dir.",
+                ),
+        )
+        .arg(
+            Arg::with_name("coherence")
+                .long("coherence")
+                .value_name("WEIGHT")
+                .takes_value(true)
+                .requires("sequence")
+                .help(
+                    "Sets how much fitness every pixel of movement of a primitive between \
+                                   frames costs. Higher values flicker less. Defaults to 50.",
+                ),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("OUTPUTFILE")
+                .takes_value(true)
+                .conflicts_with_all(&["batch", "sequence"])
+                .help(
+                    "Sets the file the painting is saved to, in the format given by its \
+                                   extension - png, jpg, bmp, ppm, pam, pbm or pgm for grayscale \
+                                   paintings, or ico up to 256 pixels. Defaults to a png named \
+                                   after the settings.",
+                ),
+        )
+        .arg(
+            Arg::with_name("output-dir")
+                .long("output-dir")
+                .value_name("DIRECTORY")
+                .takes_value(true)
+                .help(
+                    "Sets the directory paintings and samples are saved to. Batches and \
+                                   sequences also save other files such as genomes there, and \
+                                   batches a summary.csv report.",
+                ),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("EXTENSION")
+                .takes_value(true)
+                .help(
+                    "Sets the format the paintings of a batch or sequence are saved in, by \
+                                   extension, such as jpg. Defaults to png.",
+                ),
+        )
+        .arg(
+            Arg::with_name("samples")
+                .long("samples")
+                .help(
+                    "Saves two paintings of the initial population as sample.png and \
+                                   sample2.png",
+                ),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .long("jobs")
+                .value_name("JOBS")
+                .takes_value(true)
+                .requires("batch")
+                .help("Sets how many images of a batch are painted at once. Defaults to 1."),
         )
         .arg(
             Arg::with_name("iterations")
@@ -62,10 +211,292 @@ fn main() {
                 .help("Sets the verbosity level from 0 to 2")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("generator")
+                .long("generator")
+                .value_name("GENERATOR")
+                .takes_value(true)
+                .possible_values(&["informed", "gradient", "random", "pointillist"])
+                .conflicts_with_all(&["random", "gradient", "pointillist"])
+                .help(
+                    "Sets how the initial population is generated: informed (strokes \
+                                   colored like the pixel they start on), gradient (strokes \
+                                   along the edges and contours of the image), random (strokes \
+                                   colored like a random pixel) or pointillist (round dots, \
+                                   gathering in detailed areas, where the stroke count sets the \
+                                   number of dots and the stroke width their largest radius). \
+                                   Defaults to informed.",
+                ),
+        )
         .arg(Arg::with_name("random").short("r").long("random").help(
-            "Sets the generation of the initial population to be random \
-                                   instead of \"informed\"",
+            "Same as --generator random",
+        ))
+        .arg(
+            Arg::with_name("gradient")
+                .short("g")
+                .long("gradient")
+                .conflicts_with("random")
+                .help("Same as --generator gradient"),
+        )
+        .arg(
+            Arg::with_name("pointillist")
+                .long("pointillist")
+                .conflicts_with_all(&["random", "gradient"])
+                .help("Same as --generator pointillist"),
+        )
+        .arg(
+            Arg::with_name("colorsampling")
+                .long("colorsampling")
+                .value_name("SAMPLING")
+                .takes_value(true)
+                .possible_values(&["pixel", "mean", "median"])
+                .help(
+                    "Picks how informed strokes take their color from the image - from a \
+                                   single pixel, or the mean or median of the pixels they \
+                                   cover. Defaults to pixel.",
+                ),
+        )
+        .arg(Arg::with_name("recolor").long("recolor").help(
+            "Recolors every stroke of the final painting to best match the pixels it \
+                                   covers before saving",
         ))
+        .arg(
+            Arg::with_name("placement")
+                .long("placement")
+                .value_name("PLACEMENT")
+                .takes_value(true)
+                .possible_values(&["uniform", "detail"])
+                .help(
+                    "Picks where informed strokes are placed - evenly over the image, or \
+                                   concentrated, small strokes where the image is detailed \
+                                   and large ones where it is flat. Defaults to uniform.",
+                ),
+        )
+        .arg(
+            Arg::with_name("mask")
+                .long("mask")
+                .value_name("MASKFILE")
+                .takes_value(true)
+                .help(
+                    "Selects a grayscale image weighting how much each pixel matters to the \
+                                   fitness, from black (ignored) to white (full weight)",
+                ),
+        )
+        .arg(
+            Arg::with_name("maskplacement")
+                .long("maskplacement")
+                .requires("mask")
+                .help("Only places strokes on pixels the mask does not ignore"),
+        )
+        .arg(
+            Arg::with_name("background")
+                .short("b")
+                .long("background")
+                .value_name("BACKGROUND")
+                .takes_value(true)
+                .help(
+                    "Sets what the strokes are painted on - white, black, a hex color like \
+                                   #1e2a3b, mean (the average color of the image), blur or \
+                                   blur:SIGMA (a blurred copy of the image), transparent \
+                                   (saving an image with an alpha channel), or an image \
+                                   file. Defaults to white.",
+                ),
+        )
+        .arg(
+            Arg::with_name("brush")
+                .long("brush")
+                .value_name("BRUSH")
+                .takes_value(true)
+                .help(
+                    "Picks the brush strokes are painted with - flat, round, bristle, dry, \
+                                   knife, mixed (a random one of the textured brushes per \
+                                   stroke), or a grayscale texture image. Defaults to flat.",
+                ),
+        )
+        .arg(
+            Arg::with_name("widthprofile")
+                .long("widthprofile")
+                .value_name("PROFILE")
+                .takes_value(true)
+                .possible_values(&["constant", "tapered", "random"])
+                .help(
+                    "Sets how the width of new strokes changes from their start to their \
+                                   end. The profile then evolves with the stroke. Defaults \
+                                   to constant.",
+                ),
+        )
+        .arg(
+            Arg::with_name("primitives")
+                .long("primitives")
+                .value_name("KINDS")
+                .takes_value(true)
+                .help(
+                    "Sets the comma separated kinds of shape paintings are made of - stroke, \
+                                   triangle, polygon, ellipse, rectangle and dot. Defaults to \
+                                   stroke.",
+                ),
+        )
+        .arg(
+            Arg::with_name("genome")
+                .long("genome")
+                .value_name("GENOMEFILE")
+                .takes_value(true)
+                .help("Saves the primitives of the most fit painting to a text file"),
+        )
+        .arg(
+            Arg::with_name("palette")
+                .long("palette")
+                .value_name("PALETTE")
+                .takes_value(true)
+                .help(
+                    "Restricts paintings to a fixed set of colors - a comma separated list of \
+                                   hex colors like #ffffff,#1e2a3b, a file with one color per \
+                                   line, or auto:N for the N dominant colors of the image",
+                ),
+        )
+        .arg(
+            Arg::with_name("dominantcolors")
+                .long("dominantcolors")
+                .value_name("COUNT")
+                .takes_value(true)
+                .conflicts_with("palette")
+                .help(
+                    "Extracts the COUNT dominant colors of the image, prints them, and starts \
+                                   informed strokes with the dominant color closest to \
+                                   their own",
+                ),
+        )
+        .arg(
+            Arg::with_name("quantizer")
+                .long("quantizer")
+                .value_name("QUANTIZER")
+                .takes_value(true)
+                .possible_values(&["kmeans", "mediancut"])
+                .help(
+                    "Picks how dominant colors are extracted, for --dominantcolors and \
+                                   --palette auto:N. Defaults to kmeans.",
+                ),
+        )
+        .arg(
+            Arg::with_name("quantizespace")
+                .long("quantizespace")
+                .value_name("SPACE")
+                .takes_value(true)
+                .possible_values(&["rgb", "lab"])
+                .help("Picks the color space dominant colors are extracted in. Defaults to rgb."),
+        )
+        .arg(
+            Arg::with_name("exportpalette")
+                .long("exportpalette")
+                .value_name("PALETTEFILE")
+                .takes_value(true)
+                .help(
+                    "Saves the palette or dominant colors in use to a file, one hex color per \
+                                   line",
+                ),
+        )
+        .arg(
+            Arg::with_name("mixing")
+                .long("mixing")
+                .value_name("MIXING")
+                .takes_value(true)
+                .possible_values(&["alpha", "subtractive"])
+                .help(
+                    "Picks how translucent paint mixes with what is beneath it - alpha \
+                                   blending, or subtractive Kubelka-Munk pigment mixing. \
+                                   Defaults to alpha.",
+                ),
+        )
+        .arg(
+            Arg::with_name("texture")
+                .long("texture")
+                .value_name("TEXTURE")
+                .takes_value(true)
+                .help(
+                    "Overlays a surface onto the saved image - canvas, paper, or a grayscale \
+                                   texture image. Off by default.",
+                ),
+        )
+        .arg(
+            Arg::with_name("texturestrength")
+                .long("texturestrength")
+                .value_name("STRENGTH")
+                .takes_value(true)
+                .requires("texture")
+                .help("Sets how strongly the texture shows, from 0 to 1. Defaults to 0.5."),
+        )
+        .arg(
+            Arg::with_name("impasto")
+                .long("impasto")
+                .value_name("STRENGTH")
+                .takes_value(true)
+                .help(
+                    "Shades the saved image as if paint were piled up where strokes overlap, \
+                                   from 0 to 1. Off by default.",
+                ),
+        )
+        .arg(
+            Arg::with_name("jitter")
+                .long("jitter")
+                .value_name("AMOUNT")
+                .takes_value(true)
+                .help(
+                    "Randomly changes the colors of the saved image by up to the given \
+                                   amount, from 0 to 1. Off by default.",
+                ),
+        )
+        .arg(
+            Arg::with_name("grayscale")
+                .long("grayscale")
+                .help(
+                    "Paints in shades of gray, compared against the luminance of the image \
+                                   only, and saves a grayscale image.",
+                ),
+        )
+        .arg(
+            Arg::with_name("lineart")
+                .long("lineart")
+                .value_name("TARGET")
+                .takes_value(true)
+                .possible_values(&["edges", "grayscale"])
+                .conflicts_with_all(&["palette", "dominantcolors", "primitives", "pointillist"])
+                .help(
+                    "Evolves a line drawing of thin black strokes on white, compared against \
+                                   the edges of the image or the image in grayscale.",
+                ),
+        )
+        .arg(
+            Arg::with_name("linewidth")
+                .long("linewidth")
+                .value_name("WIDTH")
+                .takes_value(true)
+                .requires("lineart")
+                .help("Sets the width of every line of a line drawing. Defaults to 1."),
+        )
+        .arg(
+            Arg::with_name("plot")
+                .long("plot")
+                .value_name("PLOTFILE")
+                .takes_value(true)
+                .requires("lineart")
+                .help(
+                    "Saves the line drawing for a pen plotter, ordered to keep the pen from \
+                                   travelling far between lines, as SVG (.svg), HPGL (.hpgl, \
+                                   .plt) or G-code (.gcode, .nc, .ngc).",
+                ),
+        )
+        .arg(
+            Arg::with_name("style")
+                .long("style")
+                .value_name("STYLE")
+                .takes_value(true)
+                .help(
+                    "Picks a preset of options for a particular look - impressionist, \
+                                   pointillist, sketch, watercolour, or a style file in the \
+                                   --config format. Options given on the command line win over \
+                                   the style's.",
+                ),
+        )
         .arg(
             Arg::with_name("selector")
                 .short("e")
@@ -89,10 +520,7 @@ fn main() {
             Arg::with_name("maxcurve")
                 .short("c")
                 .long("maxcurve")
-                .help(
-                    "Sets the \
-			     maximum curve value of a stroke",
-                )
+                .help("Ignored, as strokes are always straight.")
                 .value_name("PIXELS")
                 .takes_value(true),
         )
@@ -119,30 +547,270 @@ fn main() {
                 .value_name("LENGTH"),
         )
         .get_matches();
+    // The config file may pick the style, and wins over it.
+    let config = match args.value_of("config") {
+        Some(path) => Style::load_config(path, OPTIONS)?,
+        None => Style::default(),
+    };
+    let style = match config.value_of(&args, "style").map(String::from) {
+        Some(spec) => config.over(Style::from_spec(&spec, OPTIONS)?),
+        None => config,
+    }.over(Style::defaults());
+    if args.is_present("dump-config") {
+        print!("{}", style.dump(&args, OPTIONS));
+        return Ok(());
+    }
+    let verbosity: u32 = style.parse(&args, "verbose")?;
+    println!("verbosity set to {}", verbosity);
+    if args.is_present("maxcurve") {
+        println!("--maxcurve is ignored, as strokes are always straight");
+    }
+
+    if let Some(batch) = style.value_of(&args, "batch").map(String::from) {
+        let directory = PathBuf::from(style.required(&args, "output-dir")?);
+        let jobs: usize = style.parse(&args, "jobs")?;
+        let extension = style.required(&args, "format")?.to_string();
+        return paint_batch(args.clone(), style, &batch, directory, &extension, jobs);
+    }
+    if let Some(sequence) = style.value_of(&args, "sequence") {
+        let directory = PathBuf::from(style.required(&args, "output-dir")?);
+        let extension = style.required(&args, "format")?;
+        return paint_sequence(&args, &style, sequence, &directory, extension);
+    }
+    let outputs = Outputs {
+        image: style.value_of(&args, "output").map(String::from),
+        directory: style.value_of(&args, "output-dir").map(PathBuf::from),
+        genome: style.value_of(&args, "genome").map(String::from),
+        plot: style.value_of(&args, "plot").map(String::from),
+        palette: style.value_of(&args, "exportpalette").map(String::from),
+        samples: style.is_present(&args, "samples"),
+    };
+    let image_file = style.value_of(&args, "file").ok_or_else(|| {
+        MonetError::Invalid("--file, --batch or --sequence is required".to_string())
+    })?;
+    return paint(&args, &style, image_file, &outputs);
+}
+
+/// Where the results of painting one image are saved.
+struct Outputs {
+    /// The painting, or a png named after the settings if not given.
+    image: Option<String>,
+    /// The directory the painting and samples are saved to, if not the current one.
+    directory: Option<PathBuf>,
+    genome: Option<String>,
+    plot: Option<String>,
+    palette: Option<String>,
+    /// Whether two paintings of the initial population are saved as samples.
+    samples: bool,
+}
+
+/// Paints every image of a batch into `directory`, `jobs` at a time, and writes a summary
+/// report there. An image that fails to paint is reported and doesn't stop the others, but
+/// makes the batch fail once all are done.
+fn paint_batch(
+    args: ArgMatches<'static>,
+    style: Style,
+    batch: &str,
+    directory: PathBuf,
+    extension: &str,
+    jobs: usize,
+) -> Result<()> {
+    let images = batch::find_images(batch).map_err(|error| MonetError::io(batch, error))?;
+    if images.is_empty() {
+        return Err(MonetError::Invalid(format!("no images found for the batch {}", batch)));
+    }
+    create_directory(&directory)?;
+    println!("painting {} images", images.len());
+
+    let (painting_directory, painting_extension) = (directory.clone(), extension.to_string());
+    let outcomes = driver::paint_batch(&images, jobs, move |target| {
+        let image = Path::new(&target.filename);
+        let output_path = |file: &str| {
+            batch::output_path(&painting_directory, image, file)
+                .to_string_lossy()
+                .into_owned()
+        };
+        let outputs = Outputs {
+            image: Some(batch::painting_path(&painting_directory, image, &painting_extension)
+                .to_string_lossy()
+                .into_owned()),
+            directory: None,
+            genome: Some(output_path(style.value_of(&args, "genome").unwrap_or("genome.txt"))),
+            plot: style.value_of(&args, "plot").map(&output_path),
+            palette: style.value_of(&args, "exportpalette").map(&output_path),
+            samples: false,
+        };
+        println!("painting {}", image.display());
+        return configure(&args, &style, target, &outputs);
+    });
+
+    let failed = outcomes.iter().filter(|outcome| outcome.fitness.is_err()).count();
+    let report = directory.join("summary.csv");
+    batch::save_report(&report, &directory, extension, &outcomes)
+        .map_err(|error| MonetError::io(&report.to_string_lossy(), error))?;
+    println!(
+        "painted {} of {} images, see {}",
+        outcomes.len() - failed,
+        outcomes.len(),
+        report.display()
+    );
+    if failed > 0 {
+        return Err(MonetError::Paint(
+            format!("{} of {} images could not be painted", failed, outcomes.len()),
+        ));
+    }
+    return Ok(());
+}
+
+/// Paints the frames of an animation into `directory` in order, starting the population of
+/// every frame from the most fit painting of the frame before, so strokes persist and move
+/// smoothly between frames.
+fn paint_sequence(
+    args: &ArgMatches,
+    style: &Style,
+    sequence: &str,
+    directory: &Path,
+    extension: &str,
+) -> Result<()> {
+    let frames = batch::find_images(sequence).map_err(|error| MonetError::io(sequence, error))?;
+    if frames.is_empty() {
+        return Err(MonetError::Invalid(format!("no frames found for the sequence {}", sequence)));
+    }
+    create_directory(directory)?;
+
+    let coherence: f64 = style.parse(args, "coherence")?;
+    return driver::paint_sequence(&frames, coherence, |index, target| {
+        println!("painting frame {} of {}", index + 1, frames.len());
+        let output_path = |file: &str| {
+            batch::output_path(directory, Path::new(&target.filename), file)
+                .to_string_lossy()
+                .into_owned()
+        };
+        let outputs = Outputs {
+            image: Some(directory
+                .join(format!("frame{:05}.{}", index + 1, extension))
+                .to_string_lossy()
+                .into_owned()),
+            directory: None,
+            genome: style.value_of(args, "genome").map(&output_path),
+            plot: style.value_of(args, "plot").map(&output_path),
+            palette: style.value_of(args, "exportpalette").map(&output_path),
+            samples: false,
+        };
+        return configure(args, style, target, &outputs);
+    });
+}
+
+/// Creates a directory results are saved to, along with its parents.
+fn create_directory(directory: &Path) -> Result<()> {
+    return fs::create_dir_all(directory)
+        .map_err(|error| MonetError::io(&directory.to_string_lossy(), error));
+}
+
+/// Runs the genetic algorithm on one image with the options given on the command line and
+/// by the style, and saves the results.
+fn paint(args: &ArgMatches, style: &Style, image_file: &str, outputs: &Outputs) -> Result<()> {
+    println!("generating paintings");
+    let target = Target::load(image_file)?;
+    let config = configure(args, style, &target, outputs)?;
+    let mut population_paintings = driver::populate(&target, &config)?;
+    let verbosity: u32 = style.parse(args, "verbose")?;
+    if verbosity == 2 {
+        // verbose debug prints
+        println!("{} paintings added", population_paintings.len());
+        println!("Now saving two sample images from the original population");
+    }
+    if outputs.samples {
+        println!("saving two samples");
+        population_paintings[0].render_painting(&in_directory(outputs, "sample.png"))?;
+        population_paintings[1].render_painting(&in_directory(outputs, "sample2.png"))?;
+    }
+    println!("running simulator");
+    let most_fit = driver::run(&mut population_paintings, &config)?;
+    if config.recolor {
+        println!("the most fit member, recolored, is: {}", most_fit.fitness());
+    } else {
+        println!("the most fit member is: {}", most_fit.fitness());
+    }
+    println!("saving image...");
+    driver::save(&most_fit, &config.output)?;
+    return Ok(());
+}
+
+/// Where to save a file given on the command line: in the output directory, if any.
+fn in_directory(outputs: &Outputs, file: &str) -> String {
+    return match outputs.directory {
+        Some(ref directory) => directory.join(file).to_string_lossy().into_owned(),
+        None => file.to_string(),
+    };
+}
+
+/// Builds the run for one target from the options given on the command line and by the
+/// style, checking first that its painting can be saved.
+fn configure(
+    args: &ArgMatches,
+    style: &Style,
+    target: &Target,
+    outputs: &Outputs,
+) -> Result<RunConfig> {
     // Required args.
-    let population: u32 = args.value_of("population").unwrap().parse().unwrap();
-    let number_of_strokes: u32 = args.value_of("strokes").unwrap().parse().unwrap();
-    let image_file = args.value_of("imagefile").unwrap();
+    let population: u32 = style.parse(args, "population")?;
+    let number_of_strokes: u32 = style.parse(args, "strokes")?;
 
     // Optional args.
-    let iterations: u64 = args.value_of("iterations")
-        .unwrap_or("100")
-        .parse()
-        .unwrap();
-    let verbosity: u32 = args.value_of("verbose").unwrap_or("0").parse().unwrap();
-    println!("verbosity set to {}", verbosity);
-    let random_generation: bool = args.is_present("random");
-    let width: u32 = args.value_of("strokewidth").unwrap_or("5").parse().unwrap();
-    let selector = args.value_of("selector").unwrap_or("stochastic");
-    let minlength: u32 = args.value_of("minstrokelength")
-        .unwrap_or("5")
-        .parse()
-        .unwrap();
-    let maxlength: u32 = args.value_of("maxstrokelength")
-        .unwrap_or("150")
-        .parse()
-        .unwrap();
-    let maxcurve: u32 = args.value_of("maxcurve").unwrap_or("5").parse().unwrap();
+    let iterations: u64 = style.parse(args, "iterations")?;
+    let verbosity: u32 = style.parse(args, "verbose")?;
+    let generator = style.choice(args, "generator", Generator::from_name)?;
+    let random_generation = generator == Generator::Random;
+    let color_sampling =
+        style.choice(args, "colorsampling", ColorSampling::from_name)?;
+    let recolor: bool = style.is_present(args, "recolor");
+    let placement = style.choice(args, "placement", Placement::from_name)?;
+    let mask_file = style.value_of(args, "mask");
+    let restrict_to_mask: bool = style.is_present(args, "maskplacement");
+    let background_spec = style.required(args, "background")?;
+    let brushes = Brush::from_spec(style.required(args, "brush")?)?;
+    let profile_shape =
+        style.choice(args, "widthprofile", ProfileShape::from_name)?;
+    let kinds = style.choice(args, "primitives", PrimitiveKind::from_list)?;
+    // A palette from the style gives way to dominant colors asked for on the command line.
+    let palette_spec = if args.is_present("dominantcolors") {
+        args.value_of("palette")
+    } else {
+        style.value_of(args, "palette")
+    };
+    let dominant_color_count: Option<usize> = match style.value_of(args, "dominantcolors") {
+        Some(_) => Some(style.parse(args, "dominantcolors")?),
+        None => None,
+    };
+    let quantizer = style.choice(args, "quantizer", Quantizer::from_name)?;
+    let quantize_space = style.choice(args, "quantizespace", ColorSpace::from_name)?;
+    let line_art = match style.value_of(args, "lineart") {
+        Some(_) => Some(LineArt {
+            target: style.choice(args, "lineart", LineTarget::from_name)?,
+            width: style.parse(args, "linewidth")?,
+        }),
+        None => None,
+    };
+    let grayscale: bool = style.is_present(args, "grayscale");
+    let mixing = style.choice(args, "mixing", Mixing::from_name)?;
+    let postprocess = PostProcess {
+        texture: match style.value_of(args, "texture") {
+            Some(spec) => Some(Texture::from_spec(spec)?),
+            None => None,
+        },
+        texture_strength: style.parse(args, "texturestrength")?,
+        impasto: style.parse(args, "impasto")?,
+        jitter: style.parse(args, "jitter")?,
+    };
+    let width: u32 = style.parse(args, "strokewidth")?;
+    let selector = style.required(args, "selector")?;
+    let minlength: u32 = style.parse(args, "minstrokelength")?;
+    let maxlength: u32 = style.parse(args, "maxstrokelength")?;
+    let selection = Selection::from_name(selector).unwrap_or_else(|| {
+        println!("invalid selector provided, defaulting to stochastic");
+        Selection::Stochastic
+    });
 
     println!(
         "{}",
@@ -153,90 +821,104 @@ fn main() {
                     "Parameters: \n population: {} \nnumber of strokes: {}\n \
                                image_file: {}\n iterations: {}\n random \
                                generation is {}\n stroke width: {} \n\
-			       min stroke length: {} max stroke length: {}\n \
-			       max curve: {} ",
+			       min stroke length: {} max stroke length: {}",
                     population,
                     number_of_strokes,
-                    image_file,
+                    target.filename,
                     iterations,
                     random_generation,
                     width,
                     minlength,
-                    maxlength,
-                    maxcurve
+                    maxlength
                 )
             }
 
         }
     );
 
-    println!("generating paintings");
-    let mut population_paintings: Vec<Painting> = (0..population)
-        .map(|_| if random_generation {
-            Painting::random(
-                image_file,
-                number_of_strokes,
-                width,
-                minlength,
-                maxlength,
-                maxcurve,
-            )
-        } else {
-            Painting::informed_random(image_file, number_of_strokes, width, minlength, maxlength)
-        })
-        .collect();
-    if verbosity == 2 {
-        // verbose debug prints
-        println!("{} paintings added", population_paintings.len());
-        println!("Now saving two sample images from the original population");
+    let filename = in_directory(outputs, &outputs.image.clone().unwrap_or_else(|| {
+        format!(
+            "{}_i{}_s{}_p{}_r{}_mM{}-{}.png",
+            selector,
+            iterations,
+            number_of_strokes,
+            population,
+            random_generation,
+            minlength,
+            maxlength
+        )
+    }));
+    // Fail before painting rather than after if the painting cannot be saved.
+    let color = format::color_type(background_spec == "transparent", grayscale);
+    if let Err(error) = format::check(&filename, color, target.image.dimensions()) {
+        return Err(MonetError::Invalid(format!("cannot save {}: {}", filename, error)));
     }
-    println!("saving two samples");
-    population_paintings[0].render_painting("sample.png");
-    population_paintings[1].render_painting("sample2.png");
-    let s = Simulator::builder(&mut population_paintings).set_max_iters(iterations);
-
-    // TODO figure out proper parameters and how tournament works
-    println!("making simulator");
-    let mut simulator = match selector {
-        "stochastic" => {
-            s.set_selector(Box::new(StochasticSelector::new(10)))
-                .build()
-        }
-        "maximize" => s.set_selector(Box::new(MaximizeSelector::new(10))).build(),
-        "tournament" => {
-            s.set_selector(Box::new(TournamentSelector::new((population / 4) as usize, (population / 3 + (population / 3 ) % 2) as usize)))
-                .build()
-        }
-        "parmaximize" => {
-            s.set_selector(Box::new(ParMaximizeSelector::new(((population / 3) + population % 2) as usize)))
-                .build()
+    if let Some(ref directory) = outputs.directory {
+        create_directory(directory)?;
+    }
+    let background = Background::from_spec(background_spec, &target.image)?;
+    let palette = match palette_spec {
+        Some(spec) => {
+            Some(ColorPalette::from_spec(spec, &target.image, quantizer, quantize_space)?)
         }
-        _ => {
-            println!("invalid selector provided, defaulting to stochastic");
-            s.set_selector(Box::new(StochasticSelector::new(10)))
-                .build()
+        None => None,
+    };
+    let dominant_colors = match dominant_color_count {
+        Some(count) => {
+            Some(ColorPalette::dominant(&target.image, count, quantizer, quantize_space)?)
         }
+        None => None,
     };
-
-    println!("running simulator");
-
-    simulator.run();
-    println!(
-        "the most fit member is: {}",
-        simulator.get().unwrap().fitness()
-    );
-    let most_fit = simulator.get().unwrap();
-
-    // Save the output image.
-    let filename = format!(
-        "{}_i{}_s{}_p{}_r{}_mM{}-{}.png",
-        selector,
-        iterations,
-        number_of_strokes,
-        population,
-        random_generation,
-        minlength,
-        maxlength
-    );
-    most_fit.render_and_save_image(filename);
+    let (image_width, image_height) = target.image.dimensions();
+    let mask = match mask_file {
+        Some(mask_file) => Some(Arc::new(Mask::load(mask_file, image_width, image_height)?)),
+        None => None,
+    };
+    if let Some(ref dominant_colors) = dominant_colors {
+        let colors: Vec<String> = dominant_colors.colors().iter().map(format_color).collect();
+        println!("dominant colors: {}", colors.join(" "));
+    }
+    if let Some(ref palette_export_file) = outputs.palette {
+        palette
+            .as_ref()
+            .or(dominant_colors.as_ref())
+            .ok_or_else(|| {
+                MonetError::Invalid(
+                    "--exportpalette needs --palette or --dominantcolors".to_string(),
+                )
+            })?
+            .save(palette_export_file)
+            .map_err(|error| MonetError::io(palette_export_file, error))?;
+    }
+    let config = RunConfig::builder()
+        .population(population)
+        .iterations(iterations)
+        .selection(selection)
+        .recolor(recolor)
+        .painting(PaintingConfig::builder()
+            .generator(generator)
+            .strokes(number_of_strokes)
+            .stroke_width(width)
+            .length(minlength, maxlength)
+            .color_sampling(color_sampling)
+            .placement(placement)
+            .mask(mask, restrict_to_mask)
+            .background(background)
+            .brushes(brushes)
+            .profile_shape(profile_shape)
+            .kinds(kinds)
+            .palette(palette)
+            .dominant_colors(dominant_colors)
+            .mixing(mixing)
+            .line_art(line_art)
+            .grayscale(grayscale)
+            .build()?)
+        .output(Output {
+            image: filename,
+            postprocess: postprocess,
+            genome: outputs.genome.clone(),
+            plot: outputs.plot.clone(),
+        })
+        .build()?;
+    return Ok(config);
 }
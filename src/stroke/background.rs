@@ -0,0 +1,107 @@
+use super::color::{Paint, format_color, gray, mean_color, parse_color};
+use super::point_2d::Point2D;
+use super::load_image;
+use super::error::{MonetError, Result};
+use image;
+use std::sync::Arc;
+
+/// What a painting is painted on before any stroke is drawn.
+#[derive(Clone)]
+pub enum Background {
+    /// A single flat color.
+    Color(image::Rgb<u8>),
+    /// An image the size of the canvas, such as a blurred copy of the target. Shared between
+    /// all paintings of a population.
+    Image(Arc<image::RgbImage>),
+    /// Nothing, so saved images are transparent where no paint covers the canvas. Paintings
+    /// evolve on white.
+    Transparent,
+}
+
+impl Background {
+    /// Builds the background described on the command line for the given target image:
+    /// `white`, `black` or a hex color such as `#1e2a3b`, `mean` for the average color of the
+    /// target, `blur` or `blur:SIGMA` for a blurred copy of the target, `transparent`, or else
+    /// the path of an image file to stretch over the canvas.
+    pub fn from_spec(spec: &str, target: &image::RgbImage) -> Result<Background> {
+        let (width, height) = target.dimensions();
+        if let Some(color) = parse_color(spec) {
+            return Ok(Background::Color(color));
+        }
+        if spec == "transparent" {
+            return Ok(Background::Transparent);
+        }
+        if spec == "mean" {
+            let pixels: Vec<Point2D> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| Point2D { x: x, y: y }))
+                .collect();
+            return Ok(Background::Color(mean_color(target, &pixels)));
+        }
+        if spec == "blur" || spec.starts_with("blur:") {
+            let sigma: f32 = match spec.find(':') {
+                Some(index) => {
+                    spec[index + 1..].parse().map_err(|_| {
+                        MonetError::Invalid(format!("invalid blur sigma in {}", spec))
+                    })?
+                }
+                None => 10.0,
+            };
+            return Ok(Background::Image(Arc::new(image::imageops::blur(target, sigma))));
+        }
+        let mut image = load_image(spec)?.to_rgb();
+        if image.dimensions() != (width, height) {
+            image = image::imageops::resize(&image, width, height, image::FilterType::Triangle);
+        }
+        return Ok(Background::Image(Arc::new(image)));
+    }
+
+    /// Describes the background as `from_spec` reads it, unless it is an image, whose source
+    /// isn't kept.
+    pub fn spec(&self) -> Option<String> {
+        return match *self {
+            Background::Color(ref color) => Some(format_color(color)),
+            Background::Image(_) => None,
+            Background::Transparent => Some("transparent".to_string()),
+        };
+    }
+
+    /// The background in shades of gray.
+    pub fn to_grayscale(&self) -> Background {
+        return match *self {
+            Background::Color(ref color) => Background::Color(gray(color)),
+            Background::Image(ref image) => {
+                let mut image = (**image).clone();
+                for pixel in image.pixels_mut() {
+                    *pixel = gray(pixel);
+                }
+                Background::Image(Arc::new(image))
+            }
+            Background::Transparent => Background::Transparent,
+        };
+    }
+
+    /// A fresh canvas of the given size with the background painted on.
+    pub fn canvas<P: Paint>(&self, width: u32, height: u32) -> image::ImageBuffer<P, Vec<u8>> {
+        return match *self {
+            Background::Color(color) => {
+                image::ImageBuffer::from_pixel(width, height, P::from_color(color))
+            }
+            Background::Image(ref image) => {
+                image::ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                    P::from_color(*image.get_pixel(x, y))
+                })
+            }
+            Background::Transparent => {
+                image::ImageBuffer::from_pixel(width, height, P::from_color(image::Rgb {
+                    data: [u8::max_value(); 3],
+                }))
+            }
+        };
+    }
+}
+
+impl Default for Background {
+    fn default() -> Background {
+        return Background::Color(image::Rgb { data: [u8::max_value(); 3] });
+    }
+}
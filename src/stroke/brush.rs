@@ -0,0 +1,114 @@
+use super::load_image;
+use super::error::Result;
+use image;
+use std::sync::Arc;
+
+/// Number of bristle tracks a bristle brush leaves.
+const BRISTLES: f64 = 7.0;
+
+/// The brush a stroke is painted with, deciding how much paint it leaves on every pixel it
+/// passes over.
+#[derive(Clone)]
+pub enum Brush {
+    /// A hard line of constant color, drawn as antialiased lines.
+    Flat,
+    /// A soft round brush, solid in the middle, fading at the rim and tapering at both ends.
+    Round,
+    /// Separate bristle tracks, each running out of paint at a different point.
+    Bristle,
+    /// Broken, scratchy coverage that gets sparser as the brush runs dry.
+    Dry,
+    /// A wide scrape with hard edges, square ends and streaks along its direction.
+    Knife,
+    /// A grayscale image stretched along the stroke, white leaving the most paint.
+    Texture(Arc<image::GrayImage>),
+}
+
+impl Brush {
+    /// Parses the brushes described on the command line: `flat`, `round`, `bristle`, `dry`,
+    /// `knife`, `mixed` for every procedural brush, or else the path of a grayscale texture
+    /// image. Strokes pick one of the returned brushes at random.
+    pub fn from_spec(spec: &str) -> Result<Vec<Brush>> {
+        return Ok(match spec {
+            "flat" => vec![Brush::Flat],
+            "round" => vec![Brush::Round],
+            "bristle" => vec![Brush::Bristle],
+            "dry" => vec![Brush::Dry],
+            "knife" => vec![Brush::Knife],
+            "mixed" => vec![Brush::Round, Brush::Bristle, Brush::Dry, Brush::Knife],
+            path => vec![Brush::Texture(Arc::new(load_image(path)?.to_luma()))],
+        });
+    }
+
+    /// The name of the brush, as used on the command line.
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            Brush::Flat => "flat",
+            Brush::Round => "round",
+            Brush::Bristle => "bristle",
+            Brush::Dry => "dry",
+            Brush::Knife => "knife",
+            Brush::Texture(_) => "texture",
+        };
+    }
+
+    /// How wide the brush is at `along` (0 at the start, 1 at the end of the stroke), relative
+    /// to the stroke's width.
+    pub fn taper(&self, along: f64) -> f64 {
+        return match *self {
+            Brush::Flat | Brush::Knife => 1.0,
+            _ => (along / 0.2).min((1.0 - along) / 0.2).min(1.0).max(0.0).sqrt(),
+        };
+    }
+
+    /// How much paint, from 0 to 1, the brush leaves on a pixel `along` the stroke (0 to 1) and
+    /// `across` it (-1 at one edge to 1 at the other). `distance` is how many pixels from the
+    /// start of the stroke the pixel is, and `seed` keeps the texture of a stroke stable between
+    /// renders.
+    pub fn coverage(&self, along: f64, across: f64, distance: f64, seed: u32) -> f64 {
+        return match *self {
+            Brush::Flat => 1.0,
+            Brush::Round => {
+                let rim = ((1.0 - across.abs()) / 0.3).min(1.0);
+                rim * (1.0 - 0.3 * along)
+            }
+            Brush::Bristle => {
+                let position = (across + 1.0) / 2.0 * BRISTLES;
+                let bristle = position.floor().min(BRISTLES - 1.0);
+                // Each bristle holds a different amount of paint, and runs out once used up.
+                let load = 0.5 + 0.5 * noise(seed, bristle as i64, 0);
+                if along > load {
+                    return 0.0;
+                }
+                let inside = (position - bristle - 0.5).abs() < 0.35;
+                (if inside { 1.0 } else { 0.25 }) * load
+            }
+            Brush::Dry => {
+                let grain = noise(seed, (distance / 2.0) as i64, (across * 8.0) as i64);
+                if grain < 0.25 + 0.5 * along {
+                    0.0
+                } else {
+                    0.9
+                }
+            }
+            Brush::Knife => 0.8 + 0.2 * noise(seed, 0, (across * 10.0) as i64),
+            Brush::Texture(ref texture) => {
+                let x = (along * (texture.width() - 1) as f64).round() as u32;
+                let y = ((across + 1.0) / 2.0 * (texture.height() - 1) as f64).round() as u32;
+                texture.get_pixel(x, y).data[0] as f64 / u8::max_value() as f64
+            }
+        };
+    }
+}
+
+/// A repeatable pseudo random value in [0, 1) for the given seed and coordinates.
+pub fn noise(seed: u32, a: i64, b: i64) -> f64 {
+    let mut hash = (seed as u64)
+        .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        .wrapping_add((a as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F))
+        .wrapping_add((b as u64).wrapping_mul(0x1656_67B1_9E37_79F9));
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    hash ^= hash >> 33;
+    return (hash >> 11) as f64 / (1u64 << 53) as f64;
+}
@@ -0,0 +1,150 @@
+use super::point_2d::Point2D;
+use super::primitive::Primitive;
+use image::{self, Pixel};
+
+/// How a generated stroke or shape picks its color from the target image.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorSampling {
+    /// The single pixel the stroke or shape was placed on.
+    Pixel,
+    /// The average of every pixel it covers.
+    Mean,
+    /// The per-channel median of every pixel it covers.
+    Median,
+}
+
+impl ColorSampling {
+    /// Parses the name used on the command line.
+    pub fn from_name(name: &str) -> Option<ColorSampling> {
+        return match name {
+            "pixel" => Some(ColorSampling::Pixel),
+            "mean" => Some(ColorSampling::Mean),
+            "median" => Some(ColorSampling::Median),
+            _ => None,
+        };
+    }
+
+    /// The name used on the command line.
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            ColorSampling::Pixel => "pixel",
+            ColorSampling::Mean => "mean",
+            ColorSampling::Median => "median",
+        };
+    }
+
+    /// Picks the color for a primitive, where `anchor` is the pixel it was placed on.
+    pub fn color(
+        &self,
+        image: &image::RgbImage,
+        primitive: &Primitive,
+        anchor: &Point2D,
+    ) -> image::Rgb<u8> {
+        return match *self {
+            ColorSampling::Pixel => image.get_pixel(anchor.x, anchor.y).clone(),
+            ColorSampling::Mean => {
+                mean_color(image, &primitive.footprint(image.width(), image.height()))
+            }
+            ColorSampling::Median => {
+                median_color(image, &primitive.footprint(image.width(), image.height()))
+            }
+        };
+    }
+}
+
+/// The average color of the given pixels of an image. White if there are none.
+pub fn mean_color(image: &image::RgbImage, pixels: &[Point2D]) -> image::Rgb<u8> {
+    if pixels.is_empty() {
+        return image::Rgb { data: [u8::max_value(); 3] };
+    }
+    let mut sums = [0u64; 3];
+    for pixel in pixels {
+        let rgb = image.get_pixel(pixel.x, pixel.y).data;
+        for channel in 0..3 {
+            sums[channel] += rgb[channel] as u64;
+        }
+    }
+    let count = pixels.len() as u64;
+    return image::Rgb {
+        data: [
+            (sums[0] / count) as u8,
+            (sums[1] / count) as u8,
+            (sums[2] / count) as u8,
+        ],
+    };
+}
+
+/// The per-channel median color of the given pixels of an image. White if there are none.
+pub fn median_color(image: &image::RgbImage, pixels: &[Point2D]) -> image::Rgb<u8> {
+    if pixels.is_empty() {
+        return image::Rgb { data: [u8::max_value(); 3] };
+    }
+    // Counting sort, one histogram per channel.
+    let mut histograms = [[0u32; 256]; 3];
+    for pixel in pixels {
+        let rgb = image.get_pixel(pixel.x, pixel.y).data;
+        for channel in 0..3 {
+            histograms[channel][rgb[channel] as usize] += 1;
+        }
+    }
+    let half = (pixels.len() as u32 + 1) / 2;
+    let mut median = [0u8; 3];
+    for channel in 0..3 {
+        let mut seen = 0;
+        for value in 0..256 {
+            seen += histograms[channel][value];
+            if seen >= half {
+                median[channel] = value as u8;
+                break;
+            }
+        }
+    }
+    return image::Rgb { data: median };
+}
+
+/// Parses `white`, `black` or a `#rrggbb` hex color.
+pub fn parse_color(spec: &str) -> Option<image::Rgb<u8>> {
+    match spec {
+        "white" => return Some(image::Rgb { data: [u8::max_value(); 3] }),
+        "black" => return Some(image::Rgb { data: [0; 3] }),
+        _ => (),
+    }
+    if !spec.starts_with('#') || spec.len() != 7 || !spec.is_ascii() {
+        return None;
+    }
+    let hex = &spec[1..];
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    return match (channel(0), channel(2), channel(4)) {
+        (Some(r), Some(g), Some(b)) => Some(image::Rgb { data: [r, g, b] }),
+        _ => None,
+    };
+}
+
+/// Formats a color as `#rrggbb`.
+pub fn format_color(color: &image::Rgb<u8>) -> String {
+    return format!("#{:02x}{:02x}{:02x}", color.data[0], color.data[1], color.data[2]);
+}
+
+/// The pixels primitives are painted onto: full color, or only the luminance for grayscale
+/// paintings.
+pub trait Paint: Pixel<Subpixel = u8> + 'static {
+    /// The pixel showing a color.
+    fn from_color(color: image::Rgb<u8>) -> Self;
+}
+
+impl Paint for image::Rgb<u8> {
+    fn from_color(color: image::Rgb<u8>) -> image::Rgb<u8> {
+        return color;
+    }
+}
+
+impl Paint for image::Luma<u8> {
+    fn from_color(color: image::Rgb<u8>) -> image::Luma<u8> {
+        return color.to_luma();
+    }
+}
+
+/// The shade of gray with the same luminance as a color.
+pub fn gray(color: &image::Rgb<u8>) -> image::Rgb<u8> {
+    return image::Rgb { data: [color.to_luma().data[0]; 3] };
+}
@@ -0,0 +1,132 @@
+use super::color::{format_color, parse_color};
+use super::error::{MonetError, Result};
+use super::quantize::{ColorSpace, Quantizer, dominant_colors};
+use image;
+use rand::Rng;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Number of colors extracted from the target when no count is given.
+const DEFAULT_AUTO_COLORS: usize = 8;
+
+/// A fixed set of pigments every primitive of a painting has to be painted with.
+#[derive(Clone)]
+pub struct ColorPalette {
+    colors: Vec<image::Rgb<u8>>,
+}
+
+impl ColorPalette {
+    /// Builds the palette described on the command line for the given target image: a comma
+    /// separated list of hex colors such as `#ffffff,#1e2a3b`, `auto` or `auto:N` for the N
+    /// dominant colors of the target as found by `quantizer` in `space`, or else the path of a
+    /// file with one color per line.
+    pub fn from_spec(
+        spec: &str,
+        target: &image::RgbImage,
+        quantizer: Quantizer,
+        space: ColorSpace,
+    ) -> Result<ColorPalette> {
+        let colors = if spec == "auto" || spec.starts_with("auto:") {
+            let count = match spec.find(':') {
+                Some(index) => {
+                    spec[index + 1..].parse().map_err(|_| {
+                        MonetError::Invalid(format!("invalid palette size in {}", spec))
+                    })?
+                }
+                None => DEFAULT_AUTO_COLORS,
+            };
+            dominant_colors(target, count, quantizer, space)
+        } else if Path::new(spec).is_file() {
+            let mut contents = String::new();
+            File::open(Path::new(spec))
+                .and_then(|mut file| file.read_to_string(&mut contents))
+                .map_err(|error| MonetError::io(spec, error))?;
+            parse_colors(contents.lines())?
+        } else {
+            parse_colors(spec.split(','))?
+        };
+        if colors.is_empty() {
+            return Err(MonetError::Invalid(format!("the palette {} has no colors", spec)));
+        }
+        return Ok(ColorPalette { colors: colors });
+    }
+
+    /// A palette of the given colors, of which there has to be at least one.
+    pub fn new(colors: Vec<image::Rgb<u8>>) -> Result<ColorPalette> {
+        if colors.is_empty() {
+            return Err(MonetError::Invalid("a palette needs at least one color".to_string()));
+        }
+        return Ok(ColorPalette { colors: colors });
+    }
+
+    /// A palette of a single color.
+    pub fn single(color: image::Rgb<u8>) -> ColorPalette {
+        return ColorPalette { colors: vec![color] };
+    }
+
+    /// The palette with `change` applied to every color.
+    pub fn map<F>(&self, change: F) -> ColorPalette
+    where
+        F: Fn(&image::Rgb<u8>) -> image::Rgb<u8>,
+    {
+        return ColorPalette { colors: self.colors.iter().map(change).collect() };
+    }
+
+    /// The `count` dominant colors of the target image.
+    pub fn dominant(
+        target: &image::RgbImage,
+        count: usize,
+        quantizer: Quantizer,
+        space: ColorSpace,
+    ) -> Result<ColorPalette> {
+        let colors = dominant_colors(target, count, quantizer, space);
+        if colors.is_empty() {
+            return Err(MonetError::Invalid(
+                format!("cannot extract {} dominant colors from the image", count),
+            ));
+        }
+        return Ok(ColorPalette { colors: colors });
+    }
+
+    /// The colors of the palette.
+    pub fn colors(&self) -> &[image::Rgb<u8>] {
+        return &self.colors;
+    }
+
+    /// Saves the palette as a file with one hex color per line, which `from_spec` reads back.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut file = File::create(Path::new(path))?;
+        for color in self.colors.iter() {
+            writeln!(file, "{}", format_color(color))?;
+        }
+        return Ok(());
+    }
+
+    /// The color of the palette closest to the given one.
+    pub fn nearest(&self, color: &image::Rgb<u8>) -> image::Rgb<u8> {
+        let distance = |other: &image::Rgb<u8>| -> i32 {
+            (0..3)
+                .map(|c| (color.data[c] as i32 - other.data[c] as i32).pow(2))
+                .sum()
+        };
+        return *self.colors.iter().min_by_key(|other| distance(other)).unwrap();
+    }
+
+    /// A random color of the palette.
+    pub fn random<R: Rng>(&self, rng: &mut R) -> image::Rgb<u8> {
+        return *rng.choose(&self.colors).unwrap();
+    }
+}
+
+/// Parses every non blank entry as a color.
+fn parse_colors<'a, I: Iterator<Item = &'a str>>(entries: I) -> Result<Vec<image::Rgb<u8>>> {
+    return entries
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            parse_color(entry)
+                .ok_or_else(|| MonetError::Invalid(format!("invalid palette color {}", entry)))
+        })
+        .collect();
+}
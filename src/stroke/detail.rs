@@ -0,0 +1,132 @@
+use super::gradient::box_blur;
+use super::point_2d::Point2D;
+use image;
+use imageproc::gradients::{horizontal_sobel, vertical_sobel};
+use rand::Rng;
+
+/// Share of the sampling weight every pixel gets regardless of detail, so flat regions are
+/// still painted, just more sparsely and with bigger strokes.
+const BASE_WEIGHT: f64 = 0.1;
+
+/// Where the strokes of a generated painting are placed.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Placement {
+    /// Evenly spread over the whole image.
+    Uniform,
+    /// Concentrated where the image has the most detail.
+    Detail,
+}
+
+impl Placement {
+    /// Parses the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Placement> {
+        return match name {
+            "uniform" => Some(Placement::Uniform),
+            "detail" => Some(Placement::Detail),
+            _ => None,
+        };
+    }
+
+    /// The name used on the command line.
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            Placement::Uniform => "uniform",
+            Placement::Detail => "detail",
+        };
+    }
+}
+
+/// How much detail each pixel of an image has, from 0 (flat) to 1, combining the local variance
+/// of the luminance with the local density of edges.
+pub struct DetailMap {
+    detail: Vec<f64>,
+    cumulative_weights: Vec<f64>,
+    width: u32,
+}
+
+impl DetailMap {
+    /// Builds the map for a grayscale image, looking at a (2 * radius + 1) square window around
+    /// each pixel.
+    pub fn new(image: &image::GrayImage, radius: u32) -> DetailMap {
+        let (width, height) = image.dimensions();
+        let gradient_x = horizontal_sobel(image);
+        let gradient_y = vertical_sobel(image);
+
+        let mut luminance = Vec::with_capacity((width * height) as usize);
+        let mut squared = Vec::with_capacity((width * height) as usize);
+        let mut edges = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let value = image.get_pixel(x, y).data[0] as f64;
+                luminance.push(value);
+                squared.push(value * value);
+                let dx = gradient_x.get_pixel(x, y).data[0] as f64;
+                let dy = gradient_y.get_pixel(x, y).data[0] as f64;
+                edges.push(f64::sqrt(dx * dx + dy * dy));
+            }
+        }
+        let mean = box_blur(&luminance, width, height, radius);
+        let mean_of_squares = box_blur(&squared, width, height, radius);
+        let deviation: Vec<f64> = mean.iter()
+            .zip(mean_of_squares.iter())
+            .map(|(m, s)| f64::sqrt((s - m * m).max(0.0)))
+            .collect();
+        let edges = normalize(box_blur(&edges, width, height, radius));
+        let deviation = normalize(deviation);
+
+        let detail: Vec<f64> = deviation
+            .iter()
+            .zip(edges.iter())
+            .map(|(d, e)| (d + e) / 2.0)
+            .collect();
+        let mut total = 0.0;
+        let cumulative_weights = detail
+            .iter()
+            .map(|d| {
+                total += BASE_WEIGHT + d;
+                total
+            })
+            .collect();
+
+        return DetailMap {
+            detail: detail,
+            cumulative_weights: cumulative_weights,
+            width: width,
+        };
+    }
+
+    /// The detail of the given pixel, from 0 to 1.
+    pub fn detail(&self, point: &Point2D) -> f64 {
+        return self.detail[(point.y * self.width + point.x) as usize];
+    }
+
+    /// Interpolates between `large` in flat regions and `small` in the most detailed ones.
+    pub fn scale(&self, point: &Point2D, small: u32, large: u32) -> f64 {
+        let detail = self.detail(point);
+        return large as f64 * (1.0 - detail) + small as f64 * detail;
+    }
+
+    /// Picks a random pixel, with more detailed pixels being more likely.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Point2D {
+        let total = *self.cumulative_weights.last().unwrap_or(&0.0);
+        let target = rng.gen::<f64>() * total;
+        let index = match self.cumulative_weights
+            .binary_search_by(|weight| weight.partial_cmp(&target).unwrap()) {
+            Ok(index) => index,
+            Err(index) => index.min(self.cumulative_weights.len() - 1),
+        } as u32;
+        return Point2D {
+            x: index % self.width,
+            y: index / self.width,
+        };
+    }
+}
+
+/// Scales the values so that the largest one is 1.
+fn normalize(values: Vec<f64>) -> Vec<f64> {
+    let max = values.iter().cloned().fold(0.0, f64::max);
+    if max == 0.0 {
+        return values;
+    }
+    return values.into_iter().map(|value| value / max).collect();
+}
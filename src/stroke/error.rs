@@ -0,0 +1,94 @@
+use image;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while painting.
+#[derive(Debug)]
+pub enum MonetError {
+    /// An image could not be opened or decoded.
+    Image { path: String, error: image::ImageError },
+    /// A file could not be read or written.
+    Io { path: String, error: io::Error },
+    /// A command line option, or a value in a style, palette or other file, is invalid.
+    Invalid(String),
+    /// The genetic algorithm failed, or some images of a batch could not be painted.
+    Paint(String),
+}
+
+/// The result of anything that can fail while painting.
+pub type Result<T> = ::std::result::Result<T, MonetError>;
+
+impl MonetError {
+    /// Wraps an error reading or writing the file at `path`.
+    pub fn io(path: &str, error: io::Error) -> MonetError {
+        return MonetError::Io {
+            path: path.to_string(),
+            error: error,
+        };
+    }
+
+    /// The status the program exits with: 2 for invalid options or files, 3 for files that
+    /// could not be read or written, and 1 when painting itself fails.
+    pub fn exit_code(&self) -> i32 {
+        return match *self {
+            MonetError::Invalid(_) => 2,
+            MonetError::Image { .. } |
+            MonetError::Io { .. } => 3,
+            MonetError::Paint(_) => 1,
+        };
+    }
+}
+
+impl fmt::Display for MonetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            MonetError::Image {
+                ref path,
+                error: image::ImageError::UnsupportedColor(color),
+            } if bits_per_channel(color) > 8 => {
+                write!(
+                    f,
+                    "{} has the unsupported color type {:?}; images with more than 8 bits per \
+                     channel have to be converted to 8 bits first",
+                    path,
+                    color
+                )
+            }
+            MonetError::Image {
+                ref path,
+                ref error,
+            } => write!(f, "could not open the image {}: {}", path, error),
+            MonetError::Io {
+                ref path,
+                ref error,
+            } => write!(f, "could not access {}: {}", path, error),
+            MonetError::Invalid(ref message) |
+            MonetError::Paint(ref message) => write!(f, "{}", message),
+        };
+    }
+}
+
+impl Error for MonetError {
+    fn description(&self) -> &str {
+        return match *self {
+            MonetError::Image { .. } => "could not open an image",
+            MonetError::Io { .. } => "could not access a file",
+            MonetError::Invalid(ref message) |
+            MonetError::Paint(ref message) => message,
+        };
+    }
+}
+
+/// The bits per channel, or per palette index, of a color type.
+fn bits_per_channel(color: image::ColorType) -> u8 {
+    return match color {
+        image::ColorType::Gray(bits) |
+        image::ColorType::RGB(bits) |
+        image::ColorType::Palette(bits) |
+        image::ColorType::GrayA(bits) |
+        image::ColorType::RGBA(bits) |
+        image::ColorType::BGR(bits) |
+        image::ColorType::BGRA(bits) => bits,
+    };
+}
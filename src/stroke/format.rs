@@ -0,0 +1,98 @@
+use image::ColorType;
+use std::path::Path;
+
+/// Extensions of the formats paintings can be saved as.
+const ENCODABLE: &'static [&'static str] = &[
+    "png",
+    "jpg",
+    "jpeg",
+    "bmp",
+    "ico",
+    "pbm",
+    "pgm",
+    "ppm",
+    "pam",
+];
+
+/// The largest width and height of an icon.
+const MAX_ICON_SIZE: u32 = 256;
+
+/// Extensions of image formats this version of the image crate can read but not write.
+const DECODE_ONLY: &'static [&'static str] = &["webp", "tif", "tiff", "gif", "tga", "hdr"];
+
+/// The color type paintings are saved with: with an alpha channel if `alpha`, else in shades of
+/// gray if `grayscale`, else in RGB.
+pub fn color_type(alpha: bool, grayscale: bool) -> ColorType {
+    return if alpha {
+        ColorType::RGBA(8)
+    } else if grayscale {
+        ColorType::Gray(8)
+    } else {
+        ColorType::RGB(8)
+    };
+}
+
+/// Checks, before painting, that a painting of the given size can be saved to `path` in the
+/// format given by its extension, with the given color type.
+pub fn check(path: &str, color: ColorType, (width, height): (u32, u32)) -> Result<(), String> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+        .ok_or_else(|| format!("{} has no extension to pick the image format by", path))?;
+    if DECODE_ONLY.contains(&extension.as_str()) {
+        return Err(format!(
+            "images cannot be saved as {} yet; use one of {}",
+            extension,
+            ENCODABLE.join(", ")
+        ));
+    }
+    if !ENCODABLE.contains(&extension.as_str()) {
+        return Err(format!(
+            "{} is not a known image format; use one of {}",
+            extension,
+            ENCODABLE.join(", ")
+        ));
+    }
+    let alpha = color == ColorType::RGBA(8);
+    return match extension.as_str() {
+        "jpg" | "jpeg" | "bmp" if alpha => {
+            Err(format!("{} images cannot be transparent", extension))
+        }
+        "pbm" | "pgm" if color != ColorType::Gray(8) => Err(format!(
+            "{} images can only be saved from grayscale paintings without transparency; use pam",
+            extension
+        )),
+        "ppm" if color != ColorType::RGB(8) => {
+            Err("ppm images cannot be grayscale or transparent; use pam".to_string())
+        }
+        "ico" if width > MAX_ICON_SIZE || height > MAX_ICON_SIZE => Err(format!(
+            "icons can be at most {} by {} pixels, but the painting is {} by {}",
+            MAX_ICON_SIZE,
+            MAX_ICON_SIZE,
+            width,
+            height
+        )),
+        _ => Ok(()),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, color_type};
+
+    #[test]
+    fn graymaps_need_grayscale_paintings() {
+        assert!(check("out.pgm", color_type(false, true), (10, 10)).is_ok());
+        assert!(check("out.pgm", color_type(false, false), (10, 10)).is_err());
+        assert!(check("out.pbm", color_type(true, true), (10, 10)).is_err());
+        assert!(check("out.ppm", color_type(false, true), (10, 10)).is_err());
+        assert!(check("out.pam", color_type(true, false), (10, 10)).is_ok());
+    }
+
+    #[test]
+    fn icons_are_small() {
+        assert!(check("out.ico", color_type(false, false), (256, 256)).is_ok());
+        assert!(check("out.ico", color_type(false, false), (257, 10)).is_err());
+    }
+}
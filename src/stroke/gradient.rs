@@ -0,0 +1,95 @@
+use image;
+use imageproc::gradients::{horizontal_sobel, vertical_sobel};
+use std::f64::consts::PI;
+
+/// The dominant edge direction at every pixel of an image, taken from the smoothed structure
+/// tensor of its Sobel gradients.
+pub struct OrientationField {
+    angles: Vec<f64>,
+    coherence: Vec<f64>,
+    width: u32,
+}
+
+impl OrientationField {
+    /// Builds the field for a grayscale image. `radius` is the half size of the window the
+    /// structure tensor is averaged over; larger values give smoother, longer flowing strokes.
+    pub fn new(image: &image::GrayImage, radius: u32) -> OrientationField {
+        let (width, height) = image.dimensions();
+        let gradient_x = horizontal_sobel(image);
+        let gradient_y = vertical_sobel(image);
+
+        let len = (width * height) as usize;
+        let mut jxx = vec![0f64; len];
+        let mut jxy = vec![0f64; len];
+        let mut jyy = vec![0f64; len];
+        for y in 0..height {
+            for x in 0..width {
+                let i = (y * width + x) as usize;
+                let dx = gradient_x.get_pixel(x, y).data[0] as f64;
+                let dy = gradient_y.get_pixel(x, y).data[0] as f64;
+                jxx[i] = dx * dx;
+                jxy[i] = dx * dy;
+                jyy[i] = dy * dy;
+            }
+        }
+        let jxx = box_blur(&jxx, width, height, radius);
+        let jxy = box_blur(&jxy, width, height, radius);
+        let jyy = box_blur(&jyy, width, height, radius);
+
+        let mut angles = Vec::with_capacity(len);
+        let mut coherence = Vec::with_capacity(len);
+        for i in 0..len {
+            // The tensor's major eigenvector points across the edge, so the stroke direction is
+            // perpendicular to it.
+            let gradient_angle = 0.5 * f64::atan2(2.0 * jxy[i], jxx[i] - jyy[i]);
+            angles.push(gradient_angle + PI / 2.0);
+
+            let trace = jxx[i] + jyy[i];
+            if trace > 0.0 {
+                let difference = jxx[i] - jyy[i];
+                coherence.push(f64::sqrt(difference * difference + 4.0 * jxy[i] * jxy[i]) / trace);
+            } else {
+                coherence.push(0.0);
+            }
+        }
+
+        return OrientationField {
+            angles: angles,
+            coherence: coherence,
+            width: width,
+        };
+    }
+
+    /// The angle, in radians, of the edge running through the given pixel.
+    pub fn angle(&self, x: u32, y: u32) -> f64 {
+        return self.angles[(y * self.width + x) as usize];
+    }
+
+    /// How strongly oriented the neighbourhood of a pixel is, from 0 (flat or noisy) to 1 (a
+    /// single clean edge).
+    pub fn coherence(&self, x: u32, y: u32) -> f64 {
+        return self.coherence[(y * self.width + x) as usize];
+    }
+}
+
+/// Averages every value with its neighbours in a (2 * radius + 1) square window.
+pub fn box_blur(values: &[f64], width: u32, height: u32, radius: u32) -> Vec<f64> {
+    let (width, height, radius) = (width as i64, height as i64, radius as i64);
+    let mut horizontal = vec![0f64; values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let (from, to) = ((x - radius).max(0), (x + radius).min(width - 1));
+            let sum: f64 = (from..to + 1).map(|i| values[(y * width + i) as usize]).sum();
+            horizontal[(y * width + x) as usize] = sum / (to - from + 1) as f64;
+        }
+    }
+    let mut blurred = vec![0f64; values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let (from, to) = ((y - radius).max(0), (y + radius).min(height - 1));
+            let sum: f64 = (from..to + 1).map(|i| horizontal[(i * width + x) as usize]).sum();
+            blurred[(y * width + x) as usize] = sum / (to - from + 1) as f64;
+        }
+    }
+    return blurred;
+}
@@ -0,0 +1,211 @@
+use image;
+use imageproc::edges::canny;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Thresholds on the gradient magnitude for the edge detector, below which a pixel is never an
+/// edge and above which it always is.
+const EDGE_THRESHOLDS: (f32, f32) = (30.0, 80.0);
+
+/// Plotter units, a fortieth of a millimeter, per pixel in HPGL output.
+const HPGL_UNITS_PER_PIXEL: f64 = 10.0;
+
+/// Millimeters per pixel in G-code output.
+const MILLIMETERS_PER_PIXEL: f64 = 0.25;
+
+/// How high, in millimeters, the pen is lifted between lines in G-code output.
+const PEN_UP_HEIGHT: f64 = 5.0;
+
+/// What a line drawing is compared against in the fitness.
+#[derive(Clone, Copy, PartialEq)]
+pub enum LineTarget {
+    /// The edges of the image, drawn as black lines on white.
+    Edges,
+    /// The image in shades of gray, so hatching can build up darker areas.
+    Grayscale,
+}
+
+impl LineTarget {
+    /// Parses the name used on the command line.
+    pub fn from_name(name: &str) -> Option<LineTarget> {
+        return match name {
+            "edges" => Some(LineTarget::Edges),
+            "grayscale" => Some(LineTarget::Grayscale),
+            _ => None,
+        };
+    }
+
+    /// Turns the target image into what line drawings of it are compared against.
+    pub fn apply(&self, image: &image::RgbImage) -> image::RgbImage {
+        let gray = image::imageops::grayscale(image);
+        let gray = match *self {
+            LineTarget::Edges => {
+                let mut edges = canny(&gray, EDGE_THRESHOLDS.0, EDGE_THRESHOLDS.1);
+                image::imageops::invert(&mut edges);
+                edges
+            }
+            LineTarget::Grayscale => gray,
+        };
+        return image::ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+            let value = gray.get_pixel(x, y).data[0];
+            image::Rgb { data: [value; 3] }
+        });
+    }
+}
+
+/// Restricts a painting to thin black lines on white.
+#[derive(Clone, Copy)]
+pub struct LineArt {
+    pub target: LineTarget,
+    /// The width of every line, in pixels.
+    pub width: u32,
+}
+
+/// The file formats line drawings can be exported to for pen plotters.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PlotFormat {
+    Svg,
+    Hpgl,
+    GCode,
+}
+
+impl PlotFormat {
+    /// Picks the format from the extension of a file name, such as `drawing.svg`.
+    pub fn from_path(path: &str) -> Option<PlotFormat> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        return match extension.as_ref().map(|extension| extension.as_str()) {
+            Some("svg") => Some(PlotFormat::Svg),
+            Some("hpgl") | Some("plt") => Some(PlotFormat::Hpgl),
+            Some("gcode") | Some("nc") | Some("ngc") => Some(PlotFormat::GCode),
+            _ => None,
+        };
+    }
+}
+
+/// Orders the lines so the pen travels as little as possible between them, greedily drawing the
+/// nearest line next, from whichever of its ends is closer.
+pub fn order_paths(mut paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+    let mut ordered = Vec::with_capacity(paths.len());
+    let mut position = (0.0, 0.0);
+    let distance = |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2);
+    while !paths.is_empty() {
+        let mut nearest = (0, false, ::std::f64::MAX);
+        for (i, path) in paths.iter().enumerate() {
+            let to_start = distance(position, path[0]);
+            let to_end = distance(position, path[path.len() - 1]);
+            if to_start < nearest.2 {
+                nearest = (i, false, to_start);
+            }
+            if to_end < nearest.2 {
+                nearest = (i, true, to_end);
+            }
+        }
+        let mut path = paths.swap_remove(nearest.0);
+        if nearest.1 {
+            path.reverse();
+        }
+        position = path[path.len() - 1];
+        ordered.push(path);
+    }
+    return ordered;
+}
+
+/// Writes lines drawn on a canvas of the given size to a file for a pen plotter, in the order
+/// given.
+pub fn save_plot(
+    paths: &[Vec<(f64, f64)>],
+    width: u32,
+    height: u32,
+    format: PlotFormat,
+    path: &str,
+) -> io::Result<()> {
+    let mut file = File::create(Path::new(path))?;
+    match format {
+        PlotFormat::Svg => {
+            writeln!(
+                file,
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+                 viewBox=\"0 0 {} {}\">",
+                width,
+                height,
+                width,
+                height
+            )?;
+            writeln!(file, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>")?;
+            for line in paths {
+                let points: Vec<String> = line.iter()
+                    .map(|&(x, y)| format!("{:.1},{:.1}", x, y))
+                    .collect();
+                writeln!(
+                    file,
+                    "<polyline points=\"{}\" fill=\"none\" stroke=\"black\"/>",
+                    points.join(" ")
+                )?;
+            }
+            writeln!(file, "</svg>")?;
+        }
+        PlotFormat::Hpgl => {
+            // The plotter's origin is at the bottom left, so the drawing is flipped.
+            let units = |&(x, y): &(f64, f64)| {
+                format!(
+                    "{},{}",
+                    (x * HPGL_UNITS_PER_PIXEL).round(),
+                    ((height as f64 - y) * HPGL_UNITS_PER_PIXEL).round()
+                )
+            };
+            writeln!(file, "IN;SP1;")?;
+            for line in paths {
+                writeln!(file, "PU{};", units(&line[0]))?;
+                let points: Vec<String> = line[1..].iter().map(&units).collect();
+                writeln!(file, "PD{};", points.join(","))?;
+            }
+            writeln!(file, "PU;SP0;")?;
+        }
+        PlotFormat::GCode => {
+            let millimeters = |&(x, y): &(f64, f64)| {
+                format!(
+                    "X{:.2} Y{:.2}",
+                    x * MILLIMETERS_PER_PIXEL,
+                    (height as f64 - y) * MILLIMETERS_PER_PIXEL
+                )
+            };
+            writeln!(file, "G21\nG90\nG0 Z{:.1}", PEN_UP_HEIGHT)?;
+            for line in paths {
+                writeln!(file, "G0 {}", millimeters(&line[0]))?;
+                writeln!(file, "G1 Z0 F1000")?;
+                for point in line[1..].iter() {
+                    writeln!(file, "G1 {}", millimeters(point))?;
+                }
+                writeln!(file, "G0 Z{:.1}", PEN_UP_HEIGHT)?;
+            }
+            writeln!(file, "G0 X0 Y0")?;
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::order_paths;
+
+    #[test]
+    fn paths_are_reversed_when_their_end_is_nearer() {
+        let paths = vec![
+            vec![(10.0, 0.0), (20.0, 0.0)],
+            vec![(50.0, 0.0), (21.0, 0.0)],
+            vec![(0.0, 1.0), (0.0, 9.0)],
+        ];
+        assert_eq!(
+            order_paths(paths),
+            vec![
+                vec![(0.0, 1.0), (0.0, 9.0)],
+                vec![(10.0, 0.0), (20.0, 0.0)],
+                vec![(21.0, 0.0), (50.0, 0.0)],
+            ]
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use super::load_image;
+use super::error::{MonetError, Result};
+use super::point_2d::Point2D;
+use image;
+
+/// A grayscale image weighting how much each pixel of the painting matters, from 0 (black,
+/// ignored) to 1 (white, full weight).
+pub struct Mask {
+    weights: Vec<f64>,
+    width: u32,
+}
+
+impl Mask {
+    /// Loads a mask from the given file name, stretched to the size of the painting if needed.
+    pub fn load(filename: &str, width: u32, height: u32) -> Result<Mask> {
+        let mut mask = load_image(filename)?.to_luma();
+        if mask.dimensions() != (width, height) {
+            mask = image::imageops::resize(&mask, width, height, image::FilterType::Triangle);
+        }
+        let weights: Vec<f64> = mask.pixels()
+            .map(|pixel| pixel.data[0] as f64 / u8::max_value() as f64)
+            .collect();
+        if weights.iter().all(|weight| *weight == 0.0) {
+            return Err(MonetError::Invalid(
+                format!("the mask {} does not cover any pixel", filename),
+            ));
+        }
+        return Ok(Mask {
+            weights: weights,
+            width: width,
+        });
+    }
+
+    /// The weight of the given pixel, from 0 to 1.
+    pub fn weight(&self, x: u32, y: u32) -> f64 {
+        return self.weights[(y * self.width + x) as usize];
+    }
+
+    /// Whether strokes may be placed on the given pixel.
+    pub fn covers(&self, point: &Point2D) -> bool {
+        return self.weight(point.x, point.y) > 0.0;
+    }
+}
@@ -0,0 +1,81 @@
+use super::color::Paint;
+use image::Pixel;
+use imageproc::pixelops::interpolate;
+
+/// Smallest and largest reflectance used, as Kubelka-Munk is undefined for pure black and
+/// white.
+const MIN_REFLECTANCE: f32 = 0.001;
+const MAX_REFLECTANCE: f32 = 0.999;
+
+/// How paint is mixed with what is already on the canvas where it is not fully opaque.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mixing {
+    /// Plain alpha blending of the RGB values, like light.
+    Alpha,
+    /// Kubelka-Munk mixing of pigments, where blue and yellow make green rather than gray.
+    Subtractive,
+}
+
+impl Mixing {
+    /// Parses the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Mixing> {
+        return match name {
+            "alpha" => Some(Mixing::Alpha),
+            "subtractive" => Some(Mixing::Subtractive),
+            _ => None,
+        };
+    }
+
+    /// The name used on the command line.
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            Mixing::Alpha => "alpha",
+            Mixing::Subtractive => "subtractive",
+        };
+    }
+
+    /// Mixes `paint` into `canvas`, where `weight` is the share of paint from 0 to 1.
+    pub fn blend<P: Paint>(&self, paint: P, canvas: P, weight: f32) -> P {
+        return match *self {
+            Mixing::Alpha => interpolate(paint, canvas, weight),
+            Mixing::Subtractive => kubelka_munk(paint, canvas, weight),
+        };
+    }
+}
+
+/// Mixes two colors as pigments with the single constant Kubelka-Munk model: every channel's
+/// reflectance is turned into its ratio of absorption to scattering, the ratios are mixed by
+/// weight, and the mix is turned back into reflectance.
+pub fn kubelka_munk<P: Pixel<Subpixel = u8>>(paint: P, canvas: P, weight: f32) -> P {
+    let weight = weight.max(0.0).min(1.0);
+    return paint.map2(&canvas, |paint, canvas| {
+        let absorption = weight * absorption(paint) + (1.0 - weight) * absorption(canvas);
+        let reflectance = 1.0 + absorption - (absorption * absorption + 2.0 * absorption).sqrt();
+        (reflectance * 255.0).round().max(0.0).min(255.0) as u8
+    });
+}
+
+/// The ratio of absorption to scattering, K/S, of a pigment reflecting the given channel value.
+fn absorption(channel: u8) -> f32 {
+    let reflectance = (channel as f32 / 255.0).max(MIN_REFLECTANCE).min(MAX_REFLECTANCE);
+    return (1.0 - reflectance) * (1.0 - reflectance) / (2.0 * reflectance);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mixing;
+    use image::Rgb;
+
+    /// How far green stands out from the other channels.
+    fn greenness(color: Rgb<u8>) -> i32 {
+        return color.data[1] as i32 - color.data[0].max(color.data[2]) as i32;
+    }
+
+    #[test]
+    fn blue_and_yellow_pigments_make_green() {
+        let (blue, yellow) = (Rgb { data: [40, 80, 220] }, Rgb { data: [250, 230, 30] });
+        let pigment = Mixing::Subtractive.blend(blue, yellow, 0.5);
+        let light = Mixing::Alpha.blend(blue, yellow, 0.5);
+        assert!(greenness(pigment) > 2 * greenness(light));
+    }
+}
@@ -1,13 +1,45 @@
 pub mod point_2d;
+pub mod gradient;
+pub mod color;
+pub mod detail;
+pub mod mask;
+pub mod background;
+pub mod brush;
+pub mod width_profile;
+pub mod primitive;
+pub mod quantize;
+pub mod color_palette;
+pub mod mixing;
+pub mod postprocess;
+pub mod lineart;
+pub mod format;
+pub mod error;
 use self::point_2d::Point2D;
+use self::gradient::OrientationField;
+use self::color::{Paint, format_color, gray, median_color};
+use self::detail::{DetailMap, Placement};
+use self::mask::Mask;
+use self::background::Background;
+use self::brush::Brush;
+use self::width_profile::WidthProfile;
+use self::primitive::{Dot, Primitive, PrimitiveKind};
+use self::color_palette::ColorPalette;
+use self::mixing::Mixing;
+use self::postprocess::PostProcess;
+use self::lineart::{LineArt, PlotFormat, order_paths, save_plot};
+use self::error::{MonetError, Result};
+use config::{Generator, PaintingConfig};
 // use palette::Rgb;
 use rsgenetic::pheno::*;
-use imageproc::pixelops::interpolate;
 use std::path::Path;
+use std::fs::File;
+use std::io::Write;
+use std::sync::Arc;
 use image;
 use rand::Rng;
 use rand::thread_rng;
 use imageproc::drawing::*;
+use std::f64::consts::PI;
 
 /// Represents one stroke in a painting.
 #[derive(Clone)]
@@ -17,50 +49,353 @@ pub struct Stroke {
     controls: (Point2D, Point2D),
     color: image::Rgb<u8>,
     width: u32,
+    profile: WidthProfile,
+    brush: Brush,
 }
 
-/// Represents a collection of strokes forming a painting.
+impl Stroke {
+    /// Where the stroke starts.
+    pub fn start(&self) -> &Point2D {
+        return &self.start;
+    }
+
+    /// Where the stroke ends.
+    pub fn end(&self) -> &Point2D {
+        return &self.end;
+    }
+
+    /// The color of the paint.
+    pub fn color(&self) -> image::Rgb<u8> {
+        return self.color;
+    }
+
+    /// The width of the stroke in pixels, before its width profile is applied.
+    pub fn width(&self) -> u32 {
+        return self.width;
+    }
+
+    /// The pixels this stroke covers when rendered onto a canvas of the given size.
+    fn footprint(&self, canvas_width: u32, canvas_height: u32) -> Vec<Point2D> {
+        let mut pixels = Vec::new();
+        match self.brush {
+            Brush::Flat => {
+                let dx = self.end.x as f64 - self.start.x as f64;
+                let dy = self.end.y as f64 - self.start.y as f64;
+                let steps = dx.abs().max(dy.abs()).max(1.0) as u32;
+                // Mirrors `render_strokes`, which draws one line per unit of width, each offset
+                // diagonally from the last, for as far as the width profile is wide enough.
+                for i in 0..self.profile.widest(self.width) {
+                    for step in 0..steps + 1 {
+                        let t = step as f64 / steps as f64;
+                        if self.width as f64 * self.profile.at(t) <= i as f64 {
+                            continue;
+                        }
+                        let x = ((self.start.x + i) as f64 + dx * t).round() as u32;
+                        let y = ((self.start.y + i) as f64 + dy * t).round() as u32;
+                        if x < canvas_width && y < canvas_height {
+                            pixels.push(Point2D { x: x, y: y });
+                        }
+                    }
+                }
+            }
+            _ => {
+                self.for_each_brushed_pixel(canvas_width, canvas_height, |x, y, _, _, _| {
+                    pixels.push(Point2D { x: x, y: y })
+                })
+            }
+        }
+        return pixels;
+    }
+
+    /// Calls `visit` with every pixel of the canvas under the stroke's brush, along with how far
+    /// along the stroke (0 to 1) and across it (-1 to 1) the pixel is, and its distance in
+    /// pixels from the start of the stroke.
+    fn for_each_brushed_pixel<F>(&self, canvas_width: u32, canvas_height: u32, mut visit: F)
+    where
+        F: FnMut(u32, u32, f64, f64, f64),
+    {
+        let (start_x, start_y) = (self.start.x as f64, self.start.y as f64);
+        let (dx, dy) = (self.end.x as f64 - start_x, self.end.y as f64 - start_y);
+        let length = f64::sqrt(dx * dx + dy * dy);
+        let radius = (self.width as f64 / 2.0).max(0.5);
+        // The profile may bulge a little past its largest scale between its control values.
+        let widest = self.profile.start.max(self.profile.mid).max(self.profile.end).max(1.0) *
+            radius * 1.25;
+
+        let min_x = (start_x.min(start_x + dx) - widest).floor().max(0.0) as u32;
+        let min_y = (start_y.min(start_y + dy) - widest).floor().max(0.0) as u32;
+        let max_x = ((start_x.max(start_x + dx) + widest).ceil() as u32).min(canvas_width - 1);
+        let max_y = ((start_y.max(start_y + dy) + widest).ceil() as u32).min(canvas_height - 1);
+        for y in min_y..max_y + 1 {
+            for x in min_x..max_x + 1 {
+                let (px, py) = (x as f64 - start_x, y as f64 - start_y);
+                // A stroke without length is a single dab.
+                let (along, offset) = if length == 0.0 {
+                    (0.5, f64::sqrt(px * px + py * py))
+                } else {
+                    (
+                        (px * dx + py * dy) / (length * length),
+                        (px * dy - py * dx) / length,
+                    )
+                };
+                if along < 0.0 || along > 1.0 {
+                    continue;
+                }
+                let half_width = radius * self.brush.taper(along) * self.profile.at(along);
+                if half_width <= 0.0 || offset.abs() > half_width {
+                    continue;
+                }
+                visit(x, y, along, offset / half_width, along * length);
+            }
+        }
+    }
+
+    /// The pixel `along` the way from the start to the end of the stroke (0 to 1).
+    fn point_at(&self, along: f64) -> (i32, i32) {
+        let x = self.start.x as f64 + (self.end.x as f64 - self.start.x as f64) * along;
+        let y = self.start.y as f64 + (self.end.y as f64 - self.start.y as f64) * along;
+        return (x.round() as i32, y.round() as i32);
+    }
+
+    /// Draws the line with width taken into account, or paints it with its brush, mixing
+    /// translucent paint into the canvas as given.
+    fn render<P: Paint>(&self, canvas: &mut image::ImageBuffer<P, Vec<u8>>, mixing: Mixing) {
+        match self.brush {
+            Brush::Flat => {
+                // Profiles wider than 1 thicken the stroke past its width.
+                for i in 0..self.profile.widest(self.width) {
+                    // Only the parts of the line where the width profile is wide enough.
+                    let runs = self.profile.runs_above(self.width, i as f64);
+                    for &(from, to) in runs.iter() {
+                        let (from_x, from_y) = self.point_at(from);
+                        let (to_x, to_y) = self.point_at(to);
+                        draw_antialiased_line_segment_mut(
+                            canvas,
+                            (from_x + i as i32, from_y + i as i32),
+                            (to_x + i as i32, to_y + i as i32),
+                            P::from_color(self.color),
+                            |paint, beneath, weight| mixing.blend(paint, beneath, weight),
+                        );
+                    }
+                    /*
+		draw_cubic_bezier_curve_mut(canvas,
+					(self.start.x as f32 + i as f32,
+					self.start.y as f32 + i as f32),
+					(self.end.x as f32 + i as f32, self.end.y as f32 + i as f32),
+					self.controls.0.as_tuple(), self.controls.1.as_tuple(), self.color);
+				*/
+                    // Enable this when the get control function is working
+                }
+            }
+            _ => self.paint(canvas, mixing),
+        }
+    }
+
+    /// Returns a copy of the stroke with one of its genes changed.
+    fn mutate<R: Rng>(&self, rng: &mut R, canvas_width: u32, canvas_height: u32) -> Stroke {
+        let mut to_modify = self.clone();
+
+        // Decide which part of the stroke to modify.
+        match rng.gen::<i32>() % 4 {
+            0 => {
+                to_modify.start.x = (to_modify.start.x + rng.gen::<u32>() % 30) % canvas_width;
+                to_modify.start.y = (to_modify.start.y + rng.gen::<u32>() % 30) % canvas_height;
+            }
+            1 => {
+                to_modify.end.x = (to_modify.end.x + rng.gen::<u32>() % 30) % canvas_width;
+                to_modify.end.y = (to_modify.end.y + rng.gen::<u32>() % 30) % canvas_height;
+            }
+            2 => {
+                to_modify.width = to_modify.width + rng.gen::<u32>() % 30;
+            }
+            3 => {
+                to_modify.profile = to_modify.profile.mutate(rng);
+            }
+            _ => (),
+        }
+        return to_modify;
+    }
+
+    /// Describes the stroke on a single line: its start, end and control points, color, width,
+    /// width profile and brush.
+    fn serialize(&self) -> String {
+        return format!(
+            "stroke {} {} {} {} {} {} {} {} {} {} {} {} {} {}",
+            self.start.x,
+            self.start.y,
+            self.end.x,
+            self.end.y,
+            self.controls.0.x,
+            self.controls.0.y,
+            self.controls.1.x,
+            self.controls.1.y,
+            format_color(&self.color),
+            self.width,
+            self.profile.start,
+            self.profile.mid,
+            self.profile.end,
+            self.brush.name()
+        );
+    }
+
+    /// Paints the stroke onto a canvas with its brush, mixing its color with what is already
+    /// there by how much paint the brush leaves.
+    fn paint<P: Paint>(&self, canvas: &mut image::ImageBuffer<P, Vec<u8>>, mixing: Mixing) {
+        let seed = self.start.x.wrapping_mul(73_856_093) ^ self.start.y.wrapping_mul(19_349_663);
+        let (width, height) = canvas.dimensions();
+        let brush = &self.brush;
+        let color = P::from_color(self.color);
+        self.for_each_brushed_pixel(width, height, |x, y, along, across, distance| {
+            let paint = brush.coverage(along, across, distance, seed);
+            if paint > 0.0 {
+                let blended = mixing.blend(color, *canvas.get_pixel(x, y), paint as f32);
+                canvas.put_pixel(x, y, blended);
+            }
+        });
+    }
+}
+
+/// Opens an image. Images with more than 8 bits per channel, which this version of the image
+/// crate cannot decode, fail with an error explaining so.
+pub fn load_image(path: &str) -> Result<image::DynamicImage> {
+    return image::open(&Path::new(path)).map_err(|error| {
+        MonetError::Image {
+            path: path.to_string(),
+            error: error,
+        }
+    });
+}
+
+/// The image being painted, loaded once and shared by every painting of a population.
+#[derive(Clone)]
+pub struct Target {
+    pub filename: String,
+    pub image: Arc<image::RgbImage>,
+    luma: Arc<image::GrayImage>,
+    /// The alpha channel of the image, if it has any transparent pixels.
+    opacity: Option<Arc<image::GrayImage>>,
+}
+
+impl Target {
+    /// Loads the image to paint from the given file name.
+    pub fn load(filename: &str) -> Result<Target> {
+        let image = load_image(filename)?;
+        let rgba = image.to_rgba();
+        let opacity = if rgba.pixels().all(|pixel| pixel.data[3] == u8::max_value()) {
+            None
+        } else {
+            Some(Arc::new(image::ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+                image::Luma { data: [rgba.get_pixel(x, y).data[3]] }
+            })))
+        };
+        return Ok(Target {
+            filename: filename.to_string(),
+            image: Arc::new(image.to_rgb()),
+            luma: Arc::new(image.to_luma()),
+            opacity: opacity,
+        });
+    }
+
+    /// Checks that a painting of the image can have the given number of primitives: at least
+    /// one, and no more than there are pixels.
+    fn check_count(&self, count: u32) -> Result<()> {
+        let pixels = self.image.width() * self.image.height();
+        if count == 0 || count > pixels {
+            return Err(MonetError::Invalid(format!(
+                "the number of strokes has to be between 1 and the {} pixels of {}",
+                pixels,
+                self.filename
+            )));
+        }
+        return Ok(());
+    }
+
+    /// Checks that strokes of at least `min_length` pixels fit in the image, so generators
+    /// picking random strokes until one is long enough can find one. Below the diagonal, some
+    /// stroke between pixels is from `min_length` to `min_length + 1` pixels long.
+    fn check_min_length(&self, min_length: u32) -> Result<()> {
+        let (width, height) = self.image.dimensions();
+        let diagonal = (width as f64 - 1.0).hypot(height as f64 - 1.0);
+        if min_length as f64 >= diagonal {
+            return Err(MonetError::Invalid(format!(
+                "the minimum stroke length {} has to be below the {:.0} pixel diagonal of {}",
+                min_length,
+                diagonal,
+                self.filename
+            )));
+        }
+        return Ok(());
+    }
+}
+
+/// Represents a collection of strokes and other primitives forming a painting.
 #[derive(Clone)]
 pub struct Painting {
-    strokes: Vec<Stroke>,
+    primitives: Vec<Primitive>,
     width: u32,
     height: u32,
-    filename: String,
+    target: Target,
+    /// What the painting is compared against in the fitness: the target image, or a
+    /// transformation of it such as its edges for line drawings.
+    goal: Arc<image::RgbImage>,
+    /// Weights the error of each pixel in the fitness.
+    mask: Option<Arc<Mask>>,
+    /// What the strokes are painted on.
+    background: Background,
+    /// The only colors primitives may be painted in, if restricted.
+    palette: Option<ColorPalette>,
+    /// How translucent paint mixes with what is beneath it.
+    mixing: Mixing,
+    /// Restricts the painting to a line drawing, if set.
+    line_art: Option<LineArt>,
+    /// Whether the painting is in shades of gray, compared against the luminance of the target.
+    grayscale: bool,
+    /// The primitives of the previous frame of an animation, which moving away from costs
+    /// `coherence` fitness per pixel.
+    previous: Option<Arc<Vec<Primitive>>>,
+    coherence: f64,
 }
 
 impl Painting {
-    /// Generates a Painting where the strokes are always the color of the pixel
-    /// that they start or end in. Size is the number of strokes. Min/Max length
-    /// are the minimum and maximum lengths any stroke can be.
-    pub fn informed_random(
-        filename: &str,
-        number_of_strokes: u32,
-        width: u32,
-        minlength: u32,
-        maxlength: u32,
-    ) -> Painting {
-        let image = load_image(filename);
+    /// Generates a Painting of strokes starting anywhere, colored like the pixels of the image
+    /// they start on or cover. Their number, size and look are set by `config`.
+    pub fn informed_random(target: &Target, config: &PaintingConfig) -> Result<Painting> {
+        let (width, minlength, maxlength) =
+            (config.stroke_width, config.min_length, config.max_length);
+        config.check()?;
+        target.check_count(config.strokes)?;
+        target.check_min_length(minlength)?;
+        let image = &*target.image;
+        let detail = detail_map(target, config.placement);
+        let restriction = placement_mask(&config.mask, config.restrict_to_mask);
         let num_of_pixels = image.height() * image.width();
-        let pixels_per_stroke = num_of_pixels / number_of_strokes;
+        let pixels_per_stroke = num_of_pixels / config.strokes;
         let mut rng = thread_rng();
         let mut count = 0;
-        let mut strokes: Vec<Stroke> = Vec::new();
+        let mut primitives: Vec<Primitive> = Vec::new();
         for _ in 0..num_of_pixels {
             count += 1;
             if count == pixels_per_stroke {
-                let mut stroke_length = (image.height() + image.width()) as f64;
                 let mut start = Point2D::default();
                 let mut end = Point2D::default();
                 let mut control_a = Point2D::default();
                 let mut control_b = Point2D::default();
-                while stroke_length <= minlength as f64 || stroke_length >= maxlength as f64 {
-                    start = Point2D {
-                        x: (rng.gen::<u32>() % image.width()),
-                        y: (rng.gen::<u32>() % image.height()),
+                let mut stroke_width = rng.gen::<u32>() % width + 1; /* TODO how do I determine
+                                                                      * what I want width to
+                                                                      * be? */
+                if let Some(ref detail) = detail {
+                    start = loop {
+                        let candidate = detail.sample(&mut rng);
+                        if may_place(&restriction, &candidate) {
+                            break candidate;
+                        }
                     };
+                    // Strokes running off the canvas are clipped to its border.
+                    let length = detail.scale(&start, minlength, maxlength);
+                    let angle = rng.gen::<f64>() * 2.0 * PI;
                     end = Point2D {
-                        x: (rng.gen::<u32>() % image.width()),
-                        y: (rng.gen::<u32>() % image.height()),
+                        x: clamp(start.x as f64 + angle.cos() * length, image.width()),
+                        y: clamp(start.y as f64 + angle.sin() * length, image.height()),
                     };
                     control_a = Point2D {
                         x: (rng.gen::<u32>() % image.width()),
@@ -70,53 +405,191 @@ impl Painting {
                         x: (rng.gen::<u32>() % image.width()),
                         y: (rng.gen::<u32>() % image.height()),
                     };
-                    stroke_length = f64::sqrt(
-                        ((end.x as f64 - start.x as f64) * (end.x as f64 - start.x as f64) +
-                             (end.y as f64 - start.y as f64) * (end.y as f64 - start.y as f64)) as
-                            f64,
-                    );
-                } // TODO really fix those "as f64" things
+                    stroke_width = rng.gen::<u32>() % (detail.scale(&start, 1, width) as u32) + 1;
+                } else {
+                    let mut stroke_length = (image.height() + image.width()) as f64;
+                    while stroke_length < minlength as f64 || stroke_length > maxlength as f64 ||
+                        !may_place(&restriction, &start)
+                    {
+                        start = Point2D {
+                            x: (rng.gen::<u32>() % image.width()),
+                            y: (rng.gen::<u32>() % image.height()),
+                        };
+                        end = Point2D {
+                            x: (rng.gen::<u32>() % image.width()),
+                            y: (rng.gen::<u32>() % image.height()),
+                        };
+                        control_a = Point2D {
+                            x: (rng.gen::<u32>() % image.width()),
+                            y: (rng.gen::<u32>() % image.height()),
+                        };
+                        control_b = Point2D {
+                            x: (rng.gen::<u32>() % image.width()),
+                            y: (rng.gen::<u32>() % image.height()),
+                        };
+                        stroke_length = f64::sqrt(
+                            ((end.x as f64 - start.x as f64) * (end.x as f64 - start.x as f64) +
+                                 (end.y as f64 - start.y as f64) *
+                                     (end.y as f64 - start.y as f64)) as f64,
+                        );
+                    } // TODO really fix those "as f64" things
+                }
 
-                let rgb = image.get_pixel(start.x, start.y);
+                count = 0;
+
+                let stroke = Stroke {
+                    start: start.clone(),
+                    end: end,
+                    controls: (control_a, control_b),
+                    color: image::Rgb { data: [0; 3] },
+                    width: stroke_width,
+                    profile: config.profile_shape.pick(&mut rng),
+                    brush: rng.choose(&config.brushes).unwrap().clone(),
+                };
+                let mut primitive =
+                    into_primitive(stroke, &config.kinds, &start, &image, &mut rng);
+                let color = config.color_sampling.color(&image, &primitive, &start);
+                primitive.set_color(color);
+                primitives.push(primitive);
+            }
+        }
+
+        return Ok(Painting {
+            primitives: primitives,
+            width: image.width(),
+            height: image.height(),
+            target: target.clone(),
+            goal: target.image.clone(),
+            mask: config.mask.clone(),
+            background: config.background.clone(),
+            palette: None,
+            mixing: Mixing::Alpha,
+            line_art: None,
+            grayscale: false,
+            previous: None,
+            coherence: 0.0,
+        });
+
+    }
+
+    /// Generates a Painting where every stroke follows the edges and contours of the image,
+    /// i.e. runs perpendicular to the image gradient. Everything else is set by `config`, as
+    /// for `informed_random`.
+    pub fn gradient_informed(target: &Target, config: &PaintingConfig) -> Result<Painting> {
+        let (width, minlength, maxlength) =
+            (config.stroke_width, config.min_length, config.max_length);
+        config.check()?;
+        target.check_count(config.strokes)?;
+        let image = &*target.image;
+        let field = OrientationField::new(&target.luma, 2);
+        let detail = detail_map(target, config.placement);
+        let restriction = placement_mask(&config.mask, config.restrict_to_mask);
+        let num_of_pixels = image.height() * image.width();
+        let pixels_per_stroke = num_of_pixels / config.strokes;
+        let mut rng = thread_rng();
+        let mut count = 0;
+        let mut primitives: Vec<Primitive> = Vec::new();
+        for _ in 0..num_of_pixels {
+            count += 1;
+            if count == pixels_per_stroke {
+                let center = loop {
+                    let candidate = match detail {
+                        Some(ref detail) => detail.sample(&mut rng),
+                        None => Point2D {
+                            x: (rng.gen::<u32>() % image.width()),
+                            y: (rng.gen::<u32>() % image.height()),
+                        },
+                    };
+                    if may_place(&restriction, &candidate) {
+                        break candidate;
+                    }
+                };
+                // Flat regions have no meaningful edge direction, so pick any.
+                let angle = if field.coherence(center.x, center.y) > 0.05 {
+                    field.angle(center.x, center.y)
+                } else {
+                    rng.gen::<f64>() * PI
+                };
+                let (half_length, stroke_width) = match detail {
+                    Some(ref detail) => {
+                        (
+                            detail.scale(&center, minlength, maxlength) / 2.0,
+                            rng.gen::<u32>() % (detail.scale(&center, 1, width) as u32) + 1,
+                        )
+                    }
+                    None => {
+                        (
+                            (minlength + rng.gen::<u32>() % (maxlength - minlength + 1)) as f64 /
+                                2.0,
+                            rng.gen::<u32>() % width + 1,
+                        )
+                    }
+                };
+                let (dx, dy) = (angle.cos() * half_length, angle.sin() * half_length);
+
+                // Strokes running off the canvas are clipped to its border.
+                let start = Point2D {
+                    x: clamp(center.x as f64 - dx, image.width()),
+                    y: clamp(center.y as f64 - dy, image.height()),
+                };
+                let end = Point2D {
+                    x: clamp(center.x as f64 + dx, image.width()),
+                    y: clamp(center.y as f64 + dy, image.height()),
+                };
+                let control_a = start.get_control(&end);
+                let control_b = start.get_control(&end);
 
                 count = 0;
 
-                strokes.push(Stroke {
+                let stroke = Stroke {
                     start: start,
                     end: end,
                     controls: (control_a, control_b),
-                    color: rgb.clone(),
-                    width: rng.gen::<u32>() % width + 1, /* TODO how do I determine what I want
-                                                          * width to be? */
-                });
+                    color: image::Rgb { data: [0; 3] },
+                    width: stroke_width,
+                    profile: config.profile_shape.pick(&mut rng),
+                    brush: rng.choose(&config.brushes).unwrap().clone(),
+                };
+                let mut primitive =
+                    into_primitive(stroke, &config.kinds, &center, &image, &mut rng);
+                let color = config.color_sampling.color(&image, &primitive, &center);
+                primitive.set_color(color);
+                primitives.push(primitive);
             }
         }
 
-        return Painting {
-            strokes: strokes,
+        return Ok(Painting {
+            primitives: primitives,
             width: image.width(),
             height: image.height(),
-            filename: filename.to_string(),
-        };
-
+            target: target.clone(),
+            goal: target.image.clone(),
+            mask: config.mask.clone(),
+            background: config.background.clone(),
+            palette: None,
+            mixing: Mixing::Alpha,
+            line_art: None,
+            grayscale: false,
+            previous: None,
+            coherence: 0.0,
+        });
     }
 
-    /// Randomly generates a lot of strokes within the boundaries of the size of the input image.
-    /// Width is the width of each stroke, min/max length are how short or long each line can be.
-    pub fn random(
-        filename: &str,
-        number_of_strokes: u32,
-        width: u32,
-        minlength: u32,
-        maxlength: u32,
-        _maxcurve: u32,
-    ) -> Painting {
-        let image = load_image(filename);
+    /// Generates a Painting of strokes anywhere in the image, each colored like a random pixel
+    /// of it. Their number, size and look are set by `config`.
+    pub fn random(target: &Target, config: &PaintingConfig) -> Result<Painting> {
+        let (width, minlength, maxlength) =
+            (config.stroke_width, config.min_length, config.max_length);
+        config.check()?;
+        target.check_count(config.strokes)?;
+        target.check_min_length(minlength)?;
+        let image = &*target.image;
+        let restriction = placement_mask(&config.mask, config.restrict_to_mask);
         let num_of_pixels = image.height() * image.width();
-        let pixels_per_stroke = num_of_pixels / number_of_strokes;
+        let pixels_per_stroke = num_of_pixels / config.strokes;
         let mut rng = thread_rng();
         let mut count = 0;
-        let mut strokes: Vec<Stroke> = Vec::new();
+        let mut primitives: Vec<Primitive> = Vec::new();
 
         // To achieve an evenly distributed spread of strokes, we iterate through all pixels and
         // generate one every `pixels_per_stroke` pixels.
@@ -132,7 +605,9 @@ impl Painting {
 
                 // Hacky, but continue trying until a stroke has been picked that is within the
                 // length bounds. This is in parallel anyway.
-                while stroke_length < minlength as f64 || stroke_length > maxlength as f64 {
+                while stroke_length < minlength as f64 || stroke_length > maxlength as f64 ||
+                    !may_place(&restriction, &start)
+                {
                     start = Point2D {
                         x: (rng.gen::<u32>() % image.width()),
                         y: (rng.gen::<u32>() % image.height()),
@@ -145,11 +620,8 @@ impl Painting {
                     control_a = start.get_control(&end);
                     control_b = start.get_control(&end);
 
-                    stroke_length = f64::sqrt(
-                        ((end.x - start.x) * (end.x - start.x) +
-                             (end.y - start.y) * (end.y - start.y)) as
-                            f64,
-                    );
+                    let (dx, dy) = (end.x as f64 - start.x as f64, end.y as f64 - start.y as f64);
+                    stroke_length = (dx * dx + dy * dy).sqrt();
 
                 }
 
@@ -159,92 +631,456 @@ impl Painting {
                 ); // or should this be truly random?
                 count = 0;
 
-                // Finally, push the generated stroke onto the vector of strokes.
-                strokes.push(Stroke {
-                    start: start,
+                // Finally, push the generated stroke onto the vector of primitives, or a shape
+                // in its place.
+                let stroke = Stroke {
+                    start: start.clone(),
                     end: end,
                     controls: (control_a, control_b),
                     color: rgb.clone(),
                     width: rng.gen::<u32>() % width + 1, /* TODO how do I determine what I want
                                                           * width to be? */
-                });
+                    profile: config.profile_shape.pick(&mut rng),
+                    brush: rng.choose(&config.brushes).unwrap().clone(),
+                };
+                primitives.push(into_primitive(stroke, &config.kinds, &start, &image, &mut rng));
             }
         }
 
 
-        return Painting {
-            strokes: strokes,
+        return Ok(Painting {
+            primitives: primitives,
             width: image.width(),
             height: image.height(),
-            filename: filename.to_string(),
-        };
+            target: target.clone(),
+            goal: target.image.clone(),
+            mask: config.mask.clone(),
+            background: config.background.clone(),
+            palette: None,
+            mixing: Mixing::Alpha,
+            line_art: None,
+            grayscale: false,
+            previous: None,
+            coherence: 0.0,
+        });
+    }
+
+    /// Generates a pointillist Painting of round dots, which gather in detailed areas of the
+    /// image and are smaller there. The stroke count and width of `config` set their number and
+    /// largest radius.
+    pub fn pointillist(target: &Target, config: &PaintingConfig) -> Result<Painting> {
+        config.check()?;
+        target.check_count(config.strokes)?;
+        let image = &*target.image;
+        let detail = DetailMap::new(&target.luma, 3);
+        let restriction = placement_mask(&config.mask, config.restrict_to_mask);
+        let mut rng = thread_rng();
+        let mut primitives: Vec<Primitive> = Vec::new();
+        for _ in 0..config.strokes {
+            let center = loop {
+                let candidate = detail.sample(&mut rng);
+                if may_place(&restriction, &candidate) {
+                    break candidate;
+                }
+            };
+            let largest = detail.scale(&center, 1, config.stroke_width.max(1));
+            let radius = (largest * (0.5 + rng.gen::<f64>() / 2.0)).round() as u32;
+            let unpainted = image::Rgb { data: [0; 3] };
+            let mut primitive = Primitive::Dot(Dot::new(center.clone(), radius, unpainted));
+            let color = config.color_sampling.color(&image, &primitive, &center);
+            primitive.set_color(color);
+            primitives.push(primitive);
+        }
+
+        return Ok(Painting {
+            primitives: primitives,
+            width: image.width(),
+            height: image.height(),
+            target: target.clone(),
+            goal: target.image.clone(),
+            mask: config.mask.clone(),
+            background: config.background.clone(),
+            palette: None,
+            mixing: Mixing::Alpha,
+            line_art: None,
+            grayscale: false,
+            previous: None,
+            coherence: 0.0,
+        });
     }
 
 
-    /// Render the currect strokes into an Imagebuffer.
-    fn render_strokes(&self) -> image::ImageBuffer<image::Rgb<u8>, Vec<u8>> {
-        let mut rendered_strokes_buffer =
-            image::ImageBuffer::<image::Rgb<u8>, Vec<u8>>::new(self.width, self.height);
-        for pixel in rendered_strokes_buffer.pixels_mut() {
-            pixel.data = [u8::max_value(), u8::max_value(), u8::max_value()];
-//            pixel = Image::Rgb<i8>(&mut u8::max_value();            
+    /// Generates a painting with the generator picked by `config`, then applies its colors,
+    /// mixing, line art and grayscale settings.
+    pub fn generate(target: &Target, config: &PaintingConfig) -> Result<Painting> {
+        let mut painting = match config.generator {
+            Generator::Informed => Painting::informed_random(target, config)?,
+            Generator::Gradient => Painting::gradient_informed(target, config)?,
+            Generator::Random => Painting::random(target, config)?,
+            Generator::Pointillist => Painting::pointillist(target, config)?,
+        };
+        painting.set_mixing(config.mixing);
+        if let Some(ref palette) = config.palette {
+            painting.set_palette(palette.clone());
         }
-        // draw the line with width taken into account.
-        for stroke in self.strokes.iter() {
-            for i in 0..stroke.width {
-                draw_antialiased_line_segment_mut(
-                    &mut rendered_strokes_buffer,
-                    ((stroke.start.x + i) as i32, (stroke.start.y + i) as i32),
-                    ((stroke.end.x + i) as i32, (stroke.end.y + i) as i32),
-                    stroke.color,
-                    interpolate,
-                );
-                /*
-		draw_cubic_bezier_curve_mut(&mut rendered_strokes_buffer,
-					(stroke.start.x as f32 + i as f32,
-					stroke.start.y as f32 + i as f32),
-					(stroke.end.x as f32 + i as f32, stroke.end.y as f32 + i as f32),
-					stroke.controls.0.as_tuple(), stroke.controls.1.as_tuple(), stroke.color);
-				*/
-                // Enable this when the get control function is working
+        if let Some(ref dominant_colors) = config.dominant_colors {
+            if config.generator != Generator::Random {
+                painting.snap_colors(dominant_colors);
             }
         }
+        if let Some(line_art) = config.line_art {
+            painting.set_line_art(line_art);
+        }
+        if config.grayscale {
+            painting.set_grayscale();
+        }
+        return Ok(painting);
+    }
+
+    /// The primitives of the painting, in the order they are painted.
+    pub fn primitives(&self) -> &[Primitive] {
+        return &self.primitives;
+    }
+
+    /// The width and height of the canvas, which are those of the target image.
+    pub fn dimensions(&self) -> (u32, u32) {
+        return (self.width, self.height);
+    }
+
+    /// The image being painted.
+    pub fn target(&self) -> &Target {
+        return &self.target;
+    }
+
+    /// Render the currect primitives into an Imagebuffer.
+    pub fn render_strokes(&self) -> image::ImageBuffer<image::Rgb<u8>, Vec<u8>> {
+        return self.render();
+    }
+
+    /// Renders the primitives onto the background, in full color or, for `image::Luma`, only
+    /// their luminance.
+    fn render<P: Paint>(&self) -> image::ImageBuffer<P, Vec<u8>> {
+        let mut rendered_strokes_buffer = self.background.canvas(self.width, self.height);
+        for primitive in self.primitives.iter() {
+            primitive.render(&mut rendered_strokes_buffer, self.mixing);
+        }
         return rendered_strokes_buffer;
     }
 
-    /// Save a painting to an image.
-    pub fn render_and_save_image(&self, filename: String) {
-        println!("saving image...");
-        let _ = self.render_strokes().save(Path::new(&filename));
+    /// Restricts the painting to the colors of a palette, changing the color of every primitive
+    /// to the closest one. Mutation then switches primitives between palette colors.
+    pub fn set_palette(&mut self, palette: ColorPalette) {
+        self.snap_colors(&palette);
+        self.palette = Some(palette);
+    }
+
+    /// Sets how translucent paint mixes with what is beneath it.
+    pub fn set_mixing(&mut self, mixing: Mixing) {
+        self.mixing = mixing;
+    }
+
+    /// Turns the painting into a line drawing: thin, flat black strokes on white, compared
+    /// against the target as transformed by `line_art`. Other primitives are left as they are.
+    pub fn set_line_art(&mut self, line_art: LineArt) {
+        self.background = Background::default();
+        self.set_palette(ColorPalette::single(image::Rgb { data: [0; 3] }));
+        for primitive in self.primitives.iter_mut() {
+            if let Primitive::Stroke(ref mut stroke) = *primitive {
+                stroke.width = line_art.width;
+                stroke.profile = WidthProfile::constant();
+                stroke.brush = Brush::Flat;
+            }
+        }
+        self.line_art = Some(line_art);
+        self.goal = self.goal();
+    }
+
+    /// Turns the painting into shades of gray, for charcoal or pencil studies. Every primitive,
+    /// the background and the palette take the gray of their luminance, and the fitness renders
+    /// and compares a single channel of luminance.
+    pub fn set_grayscale(&mut self) {
+        self.background = self.background.to_grayscale();
+        if let Some(ref mut palette) = self.palette {
+            *palette = palette.map(gray);
+        }
+        for primitive in self.primitives.iter_mut() {
+            let color = gray(&primitive.color());
+            primitive.set_color(color);
+        }
+        self.grayscale = true;
+        self.goal = self.goal();
+    }
+
+    /// Continues the painting as the next frame of an animation, painting `target` with the
+    /// same primitives. Every pixel a primitive moves away from where it was in this frame
+    /// costs `coherence` fitness, so the animation doesn't flicker. The painting is put on
+    /// `background`, made for `target`, unless it is line art, which stays on white.
+    pub fn follow(
+        &self,
+        target: &Target,
+        coherence: f64,
+        background: Background,
+    ) -> Result<Painting> {
+        if target.image.dimensions() != (self.width, self.height) {
+            return Err(MonetError::Invalid(format!(
+                "{} is not the same size as the previous frame",
+                target.filename
+            )));
+        }
+        let background = if self.line_art.is_some() {
+            self.background.clone()
+        } else if self.grayscale {
+            background.to_grayscale()
+        } else {
+            background
+        };
+        let mut painting = Painting {
+            target: target.clone(),
+            background: background,
+            previous: Some(Arc::new(self.primitives.clone())),
+            coherence: coherence,
+            ..self.clone()
+        };
+        painting.goal = painting.goal();
+        return Ok(painting);
+    }
+
+    /// How far the primitives moved from the previous frame. Mutation and crossover keep every
+    /// primitive at its index, so each is compared with its own previous state.
+    fn movement(&self) -> f64 {
+        return match self.previous {
+            Some(ref previous) => self.primitives
+                .iter()
+                .zip(previous.iter())
+                .map(|(primitive, before)| primitive.distance(before))
+                .sum(),
+            None => 0.0,
+        };
+    }
+
+    /// Changes the color of every primitive to the closest color of a palette, without
+    /// restricting the painting to it afterwards.
+    pub fn snap_colors(&mut self, palette: &ColorPalette) {
+        for primitive in self.primitives.iter_mut() {
+            let color = palette.nearest(&primitive.color());
+            primitive.set_color(color);
+        }
+    }
+
+    /// Sets the color of every primitive to the per-channel median of the target pixels it
+    /// covers, which is the color minimizing that primitive's contribution to the fitness error
+    /// (ignoring overlap with other primitives). With a palette, the closest palette color is
+    /// used instead.
+    pub fn recolor(&mut self) {
+        let goal = self.goal.clone();
+        for primitive in self.primitives.iter_mut() {
+            let mut color = median_color(&goal, &primitive.footprint(self.width, self.height));
+            if let Some(ref palette) = self.palette {
+                color = palette.nearest(&color);
+            }
+            primitive.set_color(color);
+        }
+    }
+
+    /// Describes the painting, one primitive per line after a header with the size of the
+    /// canvas and the target image.
+    pub fn serialize(&self) -> String {
+        let mut genome = format!(
+            "painting {} {} {}\n",
+            self.width,
+            self.height,
+            self.target.filename
+        );
+        for primitive in self.primitives.iter() {
+            genome.push_str(&primitive.serialize());
+            genome.push('\n');
+        }
+        return genome;
+    }
+
+    /// Saves the strokes of a line drawing for a pen plotter, in the format given by the
+    /// extension of `path`, ordered so the pen travels as little as possible between them.
+    pub fn save_plot(&self, path: &str) -> Result<()> {
+        let format = PlotFormat::from_path(path).ok_or_else(|| {
+            MonetError::Invalid(
+                "plots are saved as .svg, .hpgl, .plt, .gcode, .nc or .ngc files".to_string(),
+            )
+        })?;
+        let paths: Vec<Vec<(f64, f64)>> = self.primitives
+            .iter()
+            .filter_map(|primitive| match *primitive {
+                Primitive::Stroke(ref stroke) => {
+                    Some(vec![
+                        (stroke.start.x as f64, stroke.start.y as f64),
+                        (stroke.end.x as f64, stroke.end.y as f64),
+                    ])
+                }
+                _ => None,
+            })
+            .collect();
+        return save_plot(&order_paths(paths), self.width, self.height, format, path)
+            .map_err(|error| MonetError::io(path, error));
+    }
+
+    /// Save the genome of a painting to a text file.
+    pub fn save_genome(&self, path: &str) -> Result<()> {
+        return File::create(Path::new(path))
+            .and_then(|mut file| file.write_all(self.serialize().as_bytes()))
+            .map_err(|error| MonetError::io(path, error));
+    }
+
+    /// How many primitives cover every pixel of the canvas, row by row.
+    fn density(&self) -> Vec<u32> {
+        let mut density = vec![0u32; (self.width * self.height) as usize];
+        for primitive in self.primitives.iter() {
+            for pixel in primitive.footprint(self.width, self.height) {
+                density[(pixel.y * self.width + pixel.x) as usize] += 1;
+            }
+        }
+        return density;
+    }
+
+    /// Whether saved images of the painting have an alpha channel.
+    pub fn is_transparent(&self) -> bool {
+        if let Background::Transparent = self.background {
+            return true;
+        }
+        return false;
+    }
+
+    /// Whether the painting is in shades of gray, and saved as such.
+    pub fn is_grayscale(&self) -> bool {
+        return self.grayscale;
+    }
+
+    /// Save a painting to an image, with the given post-processing applied.
+    /// Paintings on a transparent background are saved with an alpha channel.
+    pub fn render_and_save_image(
+        &self,
+        filename: String,
+        postprocess: &PostProcess,
+    ) -> Result<()> {
+        let density = if postprocess.is_empty() {
+            Vec::new()
+        } else {
+            self.density()
+        };
+        let postprocessed = |mut image: image::RgbImage| {
+            if !postprocess.is_empty() {
+                postprocess.apply(&mut image, &density);
+            }
+            image
+        };
+        let render = |background: Background| {
+            let painting = Painting {
+                background: background,
+                ..self.clone()
+            };
+            painting.render_strokes()
+        };
+        let color = format::color_type(self.is_transparent(), self.grayscale);
+        let data = if let Background::Transparent = self.background {
+            let on_black = render(Background::Color(image::Rgb { data: [0; 3] }));
+            let on_white = render(Background::default());
+            let mut painted = unblend(&on_black, &on_white);
+            if !postprocess.is_empty() {
+                // The effects only change the paint, so bare canvas stays transparent.
+                let paint = postprocessed(image::ImageBuffer::from_fn(
+                    self.width,
+                    self.height,
+                    |x, y| {
+                        let pixel = painted.get_pixel(x, y).data;
+                        image::Rgb { data: [pixel[0], pixel[1], pixel[2]] }
+                    },
+                ));
+                for (x, y, pixel) in painted.enumerate_pixels_mut() {
+                    if pixel.data[3] > 0 {
+                        pixel.data[..3].copy_from_slice(&paint.get_pixel(x, y).data);
+                    }
+                }
+            }
+            painted.into_raw()
+        } else if self.grayscale {
+            image::imageops::grayscale(&postprocessed(render(self.background.clone()))).into_raw()
+        } else {
+            postprocessed(render(self.background.clone())).into_raw()
+        };
+        return image::save_buffer(Path::new(&filename), &data, self.width, self.height, color)
+            .map_err(|error| MonetError::io(&filename, error));
     }
 
     /// Save a painting to a custom filepath.
-    pub fn render_painting(&self, path: &str) {
-        println!("saving image...");
-        let _ = self.render_strokes().save(&Path::new(path));
+    pub fn render_painting(&self, path: &str) -> Result<()> {
+        return self.render_strokes()
+            .save(&Path::new(path))
+            .map_err(|error| MonetError::io(path, error));
     }
 
 
     pub fn fitness(&self) -> i32 {
-        let mut fitness = 0f64;
         // The image we are trying to approximate.
-        let goal = load_image(&self.filename);
-        let rendered_strokes_buffer = self.render_strokes();
-        for x in 0..goal.width() {
-            for y in 0..goal.height() {
-                let grgb = goal.get_pixel(x, y).data; //.iter().collect();//.map(|x| x as i32);
-                let rrgb = rendered_strokes_buffer.get_pixel(x, y);
-                let unfitness = (grgb[0] as i32 - rrgb[0] as i32).abs() +
-                    (grgb[1] as i32 - rrgb[1] as i32).abs() +
-                    (grgb[2] as i32 - rrgb[2] as i32).abs();
-                fitness += 765.0 - unfitness as f64;
+        let goal = &self.goal;
+        let mut fitness = if self.grayscale {
+            // Grayscale paintings only render their luminance, which every channel of the goal
+            // holds.
+            let rendered: image::GrayImage = self.render();
+            self.weighted_match(|x, y| {
+                3 * (goal.get_pixel(x, y).data[0] as i32 - rendered.get_pixel(x, y).data[0] as i32)
+                    .abs()
+            })
+        } else {
+            let rendered: image::RgbImage = self.render();
+            self.weighted_match(|x, y| {
+                let (grgb, rrgb) = (goal.get_pixel(x, y).data, rendered.get_pixel(x, y).data);
+                (grgb[0] as i32 - rrgb[0] as i32).abs() + (grgb[1] as i32 - rrgb[1] as i32).abs() +
+                    (grgb[2] as i32 - rrgb[2] as i32).abs()
+            })
+        };
 
-            }
+        if self.coherence > 0.0 {
+            fitness -= self.coherence * self.movement();
         }
 
         // println!("evaluated fitness as {}", fitness);
         return fitness as i32;
     }
+
+    /// How well a render matches the goal, where `error` is the difference of the pixel at x and
+    /// y summed over the channels, weighted by the mask and the opacity of the target.
+    fn weighted_match<F: Fn(u32, u32) -> i32>(&self, error: F) -> f64 {
+        let mut fitness = 0f64;
+        // Transparent parts of the target don't matter.
+        let opacity = &self.target.opacity;
+        for x in 0..self.goal.width() {
+            for y in 0..self.goal.height() {
+                let mut weight = match self.mask {
+                    Some(ref mask) => mask.weight(x, y),
+                    None => 1.0,
+                };
+                if let Some(ref opacity) = *opacity {
+                    weight *= opacity.get_pixel(x, y).data[0] as f64 / 255.0;
+                }
+                fitness += weight * (765.0 - error(x, y) as f64);
+            }
+        }
+        return fitness;
+    }
+
+    /// The image the painting is compared against: the target, its luminance for grayscale
+    /// paintings, or what line drawings of it are compared against.
+    fn goal(&self) -> Arc<image::RgbImage> {
+        if let Some(ref line_art) = self.line_art {
+            return Arc::new(line_art.target.apply(&self.target.image));
+        }
+        if self.grayscale {
+            let mut target = (*self.target.image).clone();
+            for pixel in target.pixels_mut() {
+                *pixel = gray(pixel);
+            }
+            return Arc::new(target);
+        }
+        return self.target.image.clone();
+    }
 }
 
 /// Used for the RsGenetic crate.
@@ -254,25 +1090,22 @@ impl Phenotype<i32> for Painting {
         return self.fitness();
     }
 
-    /// The "mating" function for the genetic algorithm.
+    /// The "mating" function for the genetic algorithm. Every primitive keeps its index, so
+    /// the layering and the frames of an animation stay matched.
     fn crossover(&self, other: &Painting) -> Painting {
         let s = self.clone();
         let o = other.clone();
-        let (half_of_self, _) = s.strokes.split_at(self.strokes.len() / 2);
-        let (_, half_of_other) = o.strokes.split_at(self.strokes.len() / 2);
+        let (first_of_self, second_of_self) = s.primitives.split_at(self.primitives.len() / 2);
+        let (first_of_other, second_of_other) = o.primitives.split_at(self.primitives.len() / 2);
 
         let p1 = Painting {
-            strokes: [half_of_self, half_of_other].concat(),
-            width: self.width,
-            height: self.height,
-            filename: s.filename.clone(),
+            primitives: [first_of_self, second_of_other].concat(),
+            ..self.clone()
         };
 
         let p2 = Painting {
-            strokes: [half_of_other, half_of_self].concat(),
-            width: self.width,
-            height: self.height,
-            filename: s.filename,
+            primitives: [first_of_other, second_of_self].concat(),
+            ..self.clone()
         };
 
 	let mut rng = thread_rng();
@@ -283,44 +1116,121 @@ impl Phenotype<i32> for Painting {
         }
     }
 
-    // randomly change some strokes. perhaps mutation should be dramatic.
+    // randomly change some primitives. perhaps mutation should be dramatic.
     fn mutate(&self) -> Painting {
         let mut rng = thread_rng();
         let mut s = self.clone();
 
 	for _ in 0..10 {
-		// Decide which stroke to modify.
-		let to_modify_index = rng.gen::<usize>() % self.strokes.len();
-		let mut to_modify = self.strokes[to_modify_index].clone();
-
-		
-		// Decide which part of the stroke to modify.
-		match rng.gen::<i32>() % 3 {
-		    0 => {
-			to_modify.start.x = (to_modify.start.x + rng.gen::<u32>() % 30) % self.width;
-			to_modify.start.y = (to_modify.start.y + rng.gen::<u32>() % 30) % self.height;
-		    }
-		    1 => {
-			to_modify.end.x = (to_modify.end.x + rng.gen::<u32>() % 30) % self.width;
-			to_modify.end.y = (to_modify.end.y + rng.gen::<u32>() % 30) % self.height;
+		// Decide which primitive to modify.
+		let to_modify_index = rng.gen::<usize>() % self.primitives.len();
+		let mut to_modify = match self.palette {
+		    // With a palette, sometimes switch to another pigment instead.
+		    Some(ref palette) if rng.gen::<u32>() % 5 == 0 => {
+			let mut primitive = s.primitives[to_modify_index].clone();
+			primitive.set_color(palette.random(&mut rng));
+			primitive
 		    }
-		    2 => {
-			to_modify.width = to_modify.width + rng.gen::<u32>() % 30;
+		    _ => s.primitives[to_modify_index].mutate(&mut rng, self.width, self.height),
+		};
+		// Lines of a line drawing keep their width, all along them.
+		if let Some(line_art) = self.line_art {
+		    if let Primitive::Stroke(ref mut stroke) = to_modify {
+			stroke.width = line_art.width;
+			stroke.profile = WidthProfile::constant();
 		    }
-		    _ => (),
-
 		}
 
-		s.strokes.remove(to_modify_index);
-		s.strokes.push(to_modify);
+		// In place, so the primitive keeps its identity across the frames of an animation.
+		s.primitives[to_modify_index] = to_modify;
 	}
     	return s;
     }
 }
 
-/// Load an image from the given file name.
-fn load_image(filename: &str) -> image::ImageBuffer<image::Rgb<u8>, Vec<u8>> {
-    return image::open(&Path::new(filename))
-        .expect("invalid filename when loading image")
-        .to_rgb();
+/// Recovers paint and its opacity from renders of the same painting onto black and onto white:
+/// paint lets through as much of the background as the two renders differ. Exact for alpha
+/// mixing, and close for subtractive mixing.
+fn unblend(on_black: &image::RgbImage, on_white: &image::RgbImage) -> image::RgbaImage {
+    return image::ImageBuffer::from_fn(on_black.width(), on_black.height(), |x, y| {
+        let (black, white) = (on_black.get_pixel(x, y).data, on_white.get_pixel(x, y).data);
+        let difference: f64 = (0..3)
+            .map(|channel| white[channel] as f64 - black[channel] as f64)
+            .sum::<f64>() / 3.0;
+        let alpha = (1.0 - difference / 255.0).max(0.0).min(1.0);
+        if alpha == 0.0 {
+            return image::Rgba { data: [0; 4] };
+        }
+        let channel = |i: usize| (black[i] as f64 / alpha).round().min(255.0) as u8;
+        image::Rgba { data: [channel(0), channel(1), channel(2), (alpha * 255.0).round() as u8] }
+    });
+}
+
+/// Turns a generated stroke into one of the allowed kinds of primitive, either the stroke itself
+/// or a shape of about its length and color around `anchor`.
+fn into_primitive<R: Rng>(
+    stroke: Stroke,
+    kinds: &[PrimitiveKind],
+    anchor: &Point2D,
+    image: &image::RgbImage,
+    rng: &mut R,
+) -> Primitive {
+    let kind = *rng.choose(kinds).unwrap();
+    let length = f64::sqrt(
+        (stroke.end.x as f64 - stroke.start.x as f64).powi(2) +
+            (stroke.end.y as f64 - stroke.start.y as f64).powi(2),
+    );
+    return Primitive::shape(kind, anchor, length, stroke.color, image.dimensions(), rng)
+        .unwrap_or(Primitive::Stroke(stroke));
+}
+
+/// Builds the detail map of an image when strokes should be placed by detail.
+fn detail_map(target: &Target, placement: Placement) -> Option<DetailMap> {
+    return match placement {
+        Placement::Uniform => None,
+        Placement::Detail => Some(DetailMap::new(&target.luma, 3)),
+    };
+}
+
+/// The mask strokes have to be placed on, if placement is restricted to one.
+fn placement_mask(mask: &Option<Arc<Mask>>, restrict: bool) -> Option<Arc<Mask>> {
+    return match *mask {
+        Some(ref mask) if restrict => Some(mask.clone()),
+        _ => None,
+    };
+}
+
+/// Whether a stroke may be placed on the given pixel.
+fn may_place(restriction: &Option<Arc<Mask>>, point: &Point2D) -> bool {
+    return match *restriction {
+        Some(ref mask) => mask.covers(point),
+        None => true,
+    };
+}
+
+/// Round a coordinate to the nearest pixel inside [0, size).
+fn clamp(coordinate: f64, size: u32) -> u32 {
+    return coordinate.round().max(0.0).min((size - 1) as f64) as u32;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unblend;
+    use image;
+
+    #[test]
+    fn unblend_recovers_paint_and_alpha() {
+        // Paint of (200, 100, 50) at 60% opacity, next to a pixel with no paint at all.
+        let on_black = image::ImageBuffer::from_fn(2, 1, |x, _| match x {
+            0 => image::Rgb { data: [120, 60, 30] },
+            _ => image::Rgb { data: [0, 0, 0] },
+        });
+        let on_white = image::ImageBuffer::from_fn(2, 1, |x, _| match x {
+            0 => image::Rgb { data: [222, 162, 132] },
+            _ => image::Rgb { data: [255, 255, 255] },
+        });
+        let paint = unblend(&on_black, &on_white);
+        assert_eq!(paint.get_pixel(0, 0).data, [200, 100, 50, 153]);
+        assert_eq!(paint.get_pixel(1, 0).data, [0, 0, 0, 0]);
+    }
 }
@@ -0,0 +1,159 @@
+use super::brush::noise;
+use super::load_image;
+use super::error::Result;
+use super::gradient::box_blur;
+use image;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// Seed of the noise used for paper grain and color jitter, so a painting always comes out the
+/// same.
+const SEED: u32 = 0x5EED;
+
+/// Width in pixels of a single thread of the canvas weave.
+const THREAD_WIDTH: u32 = 3;
+
+/// The surface a painting is shown on, overlaid onto the finished render.
+#[derive(Clone)]
+pub enum Texture {
+    /// Woven linen canvas.
+    Canvas,
+    /// The fine, irregular grain of watercolor paper.
+    Paper,
+    /// A grayscale image, tiled over the painting, where mid gray leaves it unchanged.
+    Image(Arc<image::GrayImage>),
+}
+
+impl Texture {
+    /// Parses `canvas`, `paper`, or else loads the texture image at the given path.
+    pub fn from_spec(spec: &str) -> Result<Texture> {
+        return Ok(match spec {
+            "canvas" => Texture::Canvas,
+            "paper" => Texture::Paper,
+            _ => Texture::Image(Arc::new(load_image(spec)?.to_luma())),
+        });
+    }
+
+    /// The height of the surface at every pixel of a canvas, from -1 (a groove) to 1 (a ridge).
+    fn heights(&self, width: u32, height: u32) -> Vec<f64> {
+        let mut heights = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                heights.push(match *self {
+                    Texture::Canvas => weave(x, y),
+                    Texture::Paper => noise(SEED, x as i64, y as i64) * 2.0 - 1.0,
+                    Texture::Image(ref texture) => {
+                        let pixel = texture.get_pixel(x % texture.width(), y % texture.height());
+                        pixel.data[0] as f64 / 127.5 - 1.0
+                    }
+                });
+            }
+        }
+        if let Texture::Paper = *self {
+            // Single pixel noise looks digital; the grain of paper is a little coarser.
+            return box_blur(&heights, width, height, 1)
+                .iter()
+                .map(|height| height * 2.0)
+                .collect();
+        }
+        return heights;
+    }
+}
+
+/// Effects applied to the finished render of a painting before it is saved. They only change
+/// the saved image, never the fitness.
+#[derive(Clone)]
+pub struct PostProcess {
+    /// The surface to overlay, if any.
+    pub texture: Option<Texture>,
+    /// How strongly the texture shows through, from 0 to 1.
+    pub texture_strength: f64,
+    /// How strongly thick paint, where many strokes overlap, is shaded as if lit from the top
+    /// left. 0 disables it.
+    pub impasto: f64,
+    /// The largest random change of every color channel, from 0 to 1.
+    pub jitter: f64,
+}
+
+impl Default for PostProcess {
+    /// No effects, with the texture strength used once a texture is set.
+    fn default() -> PostProcess {
+        return PostProcess {
+            texture: None,
+            texture_strength: 0.5,
+            impasto: 0.0,
+            jitter: 0.0,
+        };
+    }
+}
+
+impl PostProcess {
+    /// Whether applying this would leave images unchanged.
+    pub fn is_empty(&self) -> bool {
+        return (self.texture.is_none() || self.texture_strength <= 0.0) && self.impasto <= 0.0 &&
+            self.jitter <= 0.0;
+    }
+
+    /// Applies the effects to a render, where `density` is how many primitives cover each
+    /// pixel, row by row.
+    pub fn apply(&self, image: &mut image::RgbImage, density: &[u32]) {
+        let (width, height) = image.dimensions();
+        let mut shading = vec![0f64; (width * height) as usize];
+        if let Some(ref texture) = self.texture {
+            for (shade, height) in shading.iter_mut().zip(texture.heights(width, height)) {
+                *shade += self.texture_strength * 0.25 * height;
+            }
+        }
+        if self.impasto > 0.0 {
+            for (shade, lighting) in shading.iter_mut().zip(impasto(density, width, height)) {
+                *shade += self.impasto * lighting;
+            }
+        }
+        for y in 0..height {
+            for x in 0..width {
+                let shade = shading[(y * width + x) as usize];
+                let pixel = image.get_pixel_mut(x, y);
+                for channel in 0..3 {
+                    let jitter = self.jitter *
+                        (noise(SEED + channel as u32 + 1, x as i64, y as i64) * 2.0 - 1.0);
+                    let value = pixel.data[channel] as f64 / 255.0 * (1.0 + shade) + jitter;
+                    pixel.data[channel] = (value * 255.0).round().max(0.0).min(255.0) as u8;
+                }
+            }
+        }
+    }
+}
+
+/// The height of a plain weave, where threads alternate going over and under the threads
+/// crossing them.
+fn weave(x: u32, y: u32) -> f64 {
+    let (column, row) = (x / THREAD_WIDTH, y / THREAD_WIDTH);
+    // Position across the thread on top, from 0 to 1, so threads are rounded.
+    let across = if (column + row) % 2 == 0 {
+        (x % THREAD_WIDTH) as f64 + 0.5
+    } else {
+        (y % THREAD_WIDTH) as f64 + 0.5
+    } / THREAD_WIDTH as f64;
+    return (across * PI).sin() * 2.0 - 1.0;
+}
+
+/// How much brighter or darker every pixel is when paint piled up by overlapping primitives is
+/// lit from the top left, roughly from -1 to 1.
+fn impasto(density: &[u32], width: u32, height: u32) -> Vec<f64> {
+    let max = density.iter().cloned().max().unwrap_or(0).max(1) as f64;
+    let normalized: Vec<f64> = density.iter().map(|&d| d as f64 / max).collect();
+    let heights = box_blur(&normalized, width, height, 2);
+    let at = |x: i64, y: i64| {
+        let x = x.max(0).min(width as i64 - 1);
+        let y = y.max(0).min(height as i64 - 1);
+        heights[(y * width as i64 + x) as usize]
+    };
+    let mut lighting = Vec::with_capacity(heights.len());
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            // Slopes facing the top left catch the light.
+            lighting.push(((at(x + 1, y + 1) - at(x - 1, y - 1)) * 4.0).max(-1.0).min(1.0));
+        }
+    }
+    return lighting;
+}
@@ -0,0 +1,522 @@
+use super::{Stroke, clamp};
+use super::color::{Paint, format_color};
+use super::mixing::Mixing;
+use super::point_2d::Point2D;
+use image;
+use rand::Rng;
+use std::f64::consts::PI;
+
+/// The kinds of shape a painting can be made of.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PrimitiveKind {
+    Stroke,
+    Triangle,
+    Polygon,
+    Ellipse,
+    Rectangle,
+    Dot,
+}
+
+impl PrimitiveKind {
+    /// Parses the name used on the command line.
+    pub fn from_name(name: &str) -> Option<PrimitiveKind> {
+        return match name {
+            "stroke" => Some(PrimitiveKind::Stroke),
+            "triangle" => Some(PrimitiveKind::Triangle),
+            "polygon" => Some(PrimitiveKind::Polygon),
+            "ellipse" => Some(PrimitiveKind::Ellipse),
+            "rectangle" => Some(PrimitiveKind::Rectangle),
+            "dot" => Some(PrimitiveKind::Dot),
+            _ => None,
+        };
+    }
+
+    /// The name used on the command line.
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            PrimitiveKind::Stroke => "stroke",
+            PrimitiveKind::Triangle => "triangle",
+            PrimitiveKind::Polygon => "polygon",
+            PrimitiveKind::Ellipse => "ellipse",
+            PrimitiveKind::Rectangle => "rectangle",
+            PrimitiveKind::Dot => "dot",
+        };
+    }
+
+    /// Parses a comma separated list of names, such as `stroke,ellipse`.
+    pub fn from_list(list: &str) -> Option<Vec<PrimitiveKind>> {
+        return list.split(',')
+            .map(|name| PrimitiveKind::from_name(name.trim()))
+            .collect();
+    }
+}
+
+/// A filled shape with straight edges, such as a triangle.
+#[derive(Clone)]
+pub struct Polygon {
+    corners: Vec<Point2D>,
+    color: image::Rgb<u8>,
+}
+
+/// A filled, axis aligned ellipse.
+#[derive(Clone)]
+pub struct Ellipse {
+    center: Point2D,
+    radii: (u32, u32),
+    color: image::Rgb<u8>,
+}
+
+/// A filled, axis aligned rectangle.
+#[derive(Clone)]
+pub struct Rectangle {
+    corner: Point2D,
+    size: (u32, u32),
+    color: image::Rgb<u8>,
+}
+
+/// A round dab of paint, as used in pointillism.
+#[derive(Clone)]
+pub struct Dot {
+    center: Point2D,
+    radius: u32,
+    color: image::Rgb<u8>,
+}
+
+impl Dot {
+    /// A dot of the given radius, in pixels, around `center`.
+    pub fn new(center: Point2D, radius: u32, color: image::Rgb<u8>) -> Dot {
+        return Dot {
+            center: center,
+            radius: radius.max(1),
+            color: color,
+        };
+    }
+}
+
+/// One shape of a painting.
+#[derive(Clone)]
+pub enum Primitive {
+    Stroke(Stroke),
+    Triangle(Polygon),
+    Polygon(Polygon),
+    Ellipse(Ellipse),
+    Rectangle(Rectangle),
+    Dot(Dot),
+}
+
+impl Primitive {
+    /// Generates a shape of the given kind around `center`, roughly `size` pixels across, or
+    /// `None` for strokes, which only the painting generators make. Corners falling off the
+    /// canvas are clipped to its border.
+    pub fn shape<R: Rng>(
+        kind: PrimitiveKind,
+        center: &Point2D,
+        size: f64,
+        color: image::Rgb<u8>,
+        canvas: (u32, u32),
+        rng: &mut R,
+    ) -> Option<Primitive> {
+        let half = (size / 2.0).max(1.0);
+        let corners = |count: u32, rng: &mut R| -> Vec<Point2D> {
+            // Sorting by angle keeps the polygon from crossing itself.
+            let mut angles: Vec<f64> = (0..count).map(|_| rng.gen::<f64>() * 2.0 * PI).collect();
+            angles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            return angles
+                .iter()
+                .map(|angle| {
+                    let distance = half * (0.5 + rng.gen::<f64>() / 2.0);
+                    Point2D {
+                        x: clamp(center.x as f64 + angle.cos() * distance, canvas.0),
+                        y: clamp(center.y as f64 + angle.sin() * distance, canvas.1),
+                    }
+                })
+                .collect();
+        };
+        return Some(match kind {
+            PrimitiveKind::Stroke => return None,
+            PrimitiveKind::Triangle => Primitive::Triangle(Polygon {
+                corners: corners(3, rng),
+                color: color,
+            }),
+            PrimitiveKind::Polygon => {
+                let count = 4 + rng.gen::<u32>() % 3;
+                Primitive::Polygon(Polygon {
+                    corners: corners(count, rng),
+                    color: color,
+                })
+            }
+            PrimitiveKind::Ellipse => Primitive::Ellipse(Ellipse {
+                center: center.clone(),
+                radii: (
+                    (half * (0.5 + rng.gen::<f64>() / 2.0)) as u32 + 1,
+                    (half * (0.5 + rng.gen::<f64>() / 2.0)) as u32 + 1,
+                ),
+                color: color,
+            }),
+            PrimitiveKind::Rectangle => {
+                let size = (
+                    (half * (1.0 + rng.gen::<f64>())) as u32 + 1,
+                    (half * (1.0 + rng.gen::<f64>())) as u32 + 1,
+                );
+                Primitive::Rectangle(Rectangle {
+                    corner: Point2D {
+                        x: clamp(center.x as f64 - size.0 as f64 / 2.0, canvas.0),
+                        y: clamp(center.y as f64 - size.1 as f64 / 2.0, canvas.1),
+                    },
+                    size: size,
+                    color: color,
+                })
+            }
+            PrimitiveKind::Dot => {
+                let radius = (half * (0.5 + rng.gen::<f64>() / 2.0)) as u32;
+                Primitive::Dot(Dot::new(center.clone(), radius, color))
+            }
+        });
+    }
+
+    /// The color the shape is painted in.
+    pub fn color(&self) -> image::Rgb<u8> {
+        return match *self {
+            Primitive::Stroke(ref stroke) => stroke.color,
+            Primitive::Triangle(ref polygon) |
+            Primitive::Polygon(ref polygon) => polygon.color,
+            Primitive::Ellipse(ref ellipse) => ellipse.color,
+            Primitive::Rectangle(ref rectangle) => rectangle.color,
+            Primitive::Dot(ref dot) => dot.color,
+        };
+    }
+
+    /// Changes the color the shape is painted in.
+    pub fn set_color(&mut self, color: image::Rgb<u8>) {
+        match *self {
+            Primitive::Stroke(ref mut stroke) => stroke.color = color,
+            Primitive::Triangle(ref mut polygon) |
+            Primitive::Polygon(ref mut polygon) => polygon.color = color,
+            Primitive::Ellipse(ref mut ellipse) => ellipse.color = color,
+            Primitive::Rectangle(ref mut rectangle) => rectangle.color = color,
+            Primitive::Dot(ref mut dot) => dot.color = color,
+        }
+    }
+
+    /// The pixels this shape covers when rendered onto a canvas of the given size.
+    pub fn footprint(&self, canvas_width: u32, canvas_height: u32) -> Vec<Point2D> {
+        let mut pixels = Vec::new();
+        match *self {
+            Primitive::Stroke(ref stroke) => return stroke.footprint(canvas_width, canvas_height),
+            Primitive::Triangle(ref polygon) |
+            Primitive::Polygon(ref polygon) => {
+                polygon.for_each_pixel(canvas_width, canvas_height, |x, y| {
+                    pixels.push(Point2D { x: x, y: y })
+                })
+            }
+            Primitive::Ellipse(ref ellipse) => {
+                ellipse.for_each_pixel(canvas_width, canvas_height, |x, y| {
+                    pixels.push(Point2D { x: x, y: y })
+                })
+            }
+            Primitive::Rectangle(ref rectangle) => {
+                rectangle.for_each_pixel(canvas_width, canvas_height, |x, y| {
+                    pixels.push(Point2D { x: x, y: y })
+                })
+            }
+            Primitive::Dot(ref dot) => {
+                dot.for_each_pixel(canvas_width, canvas_height, |x, y| {
+                    pixels.push(Point2D { x: x, y: y })
+                })
+            }
+        }
+        return pixels;
+    }
+
+    /// How far, in pixels, the shape is from another one, such as its counterpart in the
+    /// previous frame of an animation. Strokes compare both ends, other shapes their centers.
+    pub fn distance(&self, other: &Primitive) -> f64 {
+        if let (&Primitive::Stroke(ref a), &Primitive::Stroke(ref b)) = (self, other) {
+            return (between(position(&a.start), position(&b.start)) +
+                        between(position(&a.end), position(&b.end))) / 2.0;
+        }
+        return between(self.center(), other.center());
+    }
+
+    /// The middle of the shape.
+    fn center(&self) -> (f64, f64) {
+        return match *self {
+            Primitive::Stroke(ref stroke) => {
+                (
+                    (stroke.start.x + stroke.end.x) as f64 / 2.0,
+                    (stroke.start.y + stroke.end.y) as f64 / 2.0,
+                )
+            }
+            Primitive::Triangle(ref polygon) |
+            Primitive::Polygon(ref polygon) => {
+                let count = polygon.corners.len().max(1) as f64;
+                (
+                    polygon.corners.iter().map(|corner| corner.x as f64).sum::<f64>() / count,
+                    polygon.corners.iter().map(|corner| corner.y as f64).sum::<f64>() / count,
+                )
+            }
+            Primitive::Ellipse(ref ellipse) => position(&ellipse.center),
+            Primitive::Rectangle(ref rectangle) => {
+                (
+                    rectangle.corner.x as f64 + rectangle.size.0 as f64 / 2.0,
+                    rectangle.corner.y as f64 + rectangle.size.1 as f64 / 2.0,
+                )
+            }
+            Primitive::Dot(ref dot) => position(&dot.center),
+        };
+    }
+
+    /// Draws the shape onto a canvas. Shapes are opaque, so only strokes use `mixing`.
+    pub fn render<P: Paint>(&self, canvas: &mut image::ImageBuffer<P, Vec<u8>>, mixing: Mixing) {
+        if let Primitive::Stroke(ref stroke) = *self {
+            return stroke.render(canvas, mixing);
+        }
+        let (width, height) = canvas.dimensions();
+        let color = P::from_color(self.color());
+        for pixel in self.footprint(width, height) {
+            canvas.put_pixel(pixel.x, pixel.y, color);
+        }
+    }
+
+    /// Returns a copy of the shape with one of its genes changed.
+    pub fn mutate<R: Rng>(&self, rng: &mut R, canvas_width: u32, canvas_height: u32) -> Primitive {
+        let mut mutated = self.clone();
+        match mutated {
+            Primitive::Stroke(ref mut stroke) => {
+                *stroke = stroke.mutate(rng, canvas_width, canvas_height)
+            }
+            Primitive::Triangle(ref mut polygon) |
+            Primitive::Polygon(ref mut polygon) => {
+                let index = rng.gen::<usize>() % polygon.corners.len();
+                let corner = &mut polygon.corners[index];
+                *corner = nudge(corner, rng, canvas_width, canvas_height);
+            }
+            Primitive::Ellipse(ref mut ellipse) => {
+                match rng.gen::<u32>() % 3 {
+                    0 => ellipse.center = nudge(&ellipse.center, rng, canvas_width, canvas_height),
+                    1 => ellipse.radii.0 = resize(ellipse.radii.0, rng),
+                    _ => ellipse.radii.1 = resize(ellipse.radii.1, rng),
+                }
+            }
+            Primitive::Rectangle(ref mut rectangle) => {
+                match rng.gen::<u32>() % 3 {
+                    0 => {
+                        rectangle.corner =
+                            nudge(&rectangle.corner, rng, canvas_width, canvas_height)
+                    }
+                    1 => rectangle.size.0 = resize(rectangle.size.0, rng),
+                    _ => rectangle.size.1 = resize(rectangle.size.1, rng),
+                }
+            }
+            Primitive::Dot(ref mut dot) => {
+                if rng.gen() {
+                    dot.center = nudge(&dot.center, rng, canvas_width, canvas_height);
+                } else {
+                    dot.radius = resize(dot.radius, rng);
+                }
+            }
+        }
+        return mutated;
+    }
+
+    /// Describes the shape on a single line, starting with its kind and followed by its genes.
+    pub fn serialize(&self) -> String {
+        return match *self {
+            Primitive::Stroke(ref stroke) => stroke.serialize(),
+            Primitive::Triangle(ref polygon) => format!("triangle {}", polygon.serialize()),
+            Primitive::Polygon(ref polygon) => format!("polygon {}", polygon.serialize()),
+            Primitive::Ellipse(ref ellipse) => {
+                format!(
+                    "ellipse {} {} {} {} {}",
+                    ellipse.center.x,
+                    ellipse.center.y,
+                    ellipse.radii.0,
+                    ellipse.radii.1,
+                    format_color(&ellipse.color)
+                )
+            }
+            Primitive::Rectangle(ref rectangle) => {
+                format!(
+                    "rectangle {} {} {} {} {}",
+                    rectangle.corner.x,
+                    rectangle.corner.y,
+                    rectangle.size.0,
+                    rectangle.size.1,
+                    format_color(&rectangle.color)
+                )
+            }
+            Primitive::Dot(ref dot) => {
+                format!(
+                    "dot {} {} {} {}",
+                    dot.center.x,
+                    dot.center.y,
+                    dot.radius,
+                    format_color(&dot.color)
+                )
+            }
+        };
+    }
+}
+
+impl Polygon {
+    /// Calls `visit` with every pixel of the canvas inside the polygon, using the even-odd rule.
+    fn for_each_pixel<F>(&self, canvas_width: u32, canvas_height: u32, mut visit: F)
+    where
+        F: FnMut(u32, u32),
+    {
+        let min_y = self.corners.iter().map(|corner| corner.y).min().unwrap_or(0);
+        let max_y = self.corners.iter().map(|corner| corner.y).max().unwrap_or(0);
+        for y in min_y..max_y.min(canvas_height - 1) + 1 {
+            // Sample the middle of the row so corners are never hit exactly.
+            let row = y as f64 + 0.5;
+            let mut crossings = Vec::new();
+            for (i, a) in self.corners.iter().enumerate() {
+                let b = &self.corners[(i + 1) % self.corners.len()];
+                let (ay, by) = (a.y as f64, b.y as f64);
+                if (ay <= row) != (by <= row) {
+                    crossings.push(a.x as f64 + (row - ay) / (by - ay) * (b.x as f64 - a.x as f64));
+                }
+            }
+            crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            for pair in crossings.chunks(2) {
+                if pair.len() < 2 {
+                    continue;
+                }
+                let from = pair[0].round().max(0.0) as u32;
+                let to = (pair[1].round() as u32).min(canvas_width);
+                for x in from..to {
+                    visit(x, y);
+                }
+            }
+        }
+    }
+
+    /// The polygon's corners followed by its color.
+    fn serialize(&self) -> String {
+        let corners: Vec<String> = self.corners
+            .iter()
+            .map(|corner| format!("{} {}", corner.x, corner.y))
+            .collect();
+        return format!("{} {}", corners.join(" "), format_color(&self.color));
+    }
+}
+
+impl Ellipse {
+    /// Calls `visit` with every pixel of the canvas inside the ellipse.
+    fn for_each_pixel<F>(&self, canvas_width: u32, canvas_height: u32, mut visit: F)
+    where
+        F: FnMut(u32, u32),
+    {
+        let (rx, ry) = (self.radii.0.max(1) as f64, self.radii.1.max(1) as f64);
+        let min_x = self.center.x.saturating_sub(self.radii.0);
+        let min_y = self.center.y.saturating_sub(self.radii.1);
+        let max_x = (self.center.x + self.radii.0).min(canvas_width - 1);
+        let max_y = (self.center.y + self.radii.1).min(canvas_height - 1);
+        for y in min_y..max_y + 1 {
+            for x in min_x..max_x + 1 {
+                let dx = (x as f64 - self.center.x as f64) / rx;
+                let dy = (y as f64 - self.center.y as f64) / ry;
+                if dx * dx + dy * dy <= 1.0 {
+                    visit(x, y);
+                }
+            }
+        }
+    }
+}
+
+impl Rectangle {
+    /// Calls `visit` with every pixel of the canvas inside the rectangle.
+    fn for_each_pixel<F>(&self, canvas_width: u32, canvas_height: u32, mut visit: F)
+    where
+        F: FnMut(u32, u32),
+    {
+        let max_x = (self.corner.x + self.size.0).min(canvas_width);
+        let max_y = (self.corner.y + self.size.1).min(canvas_height);
+        for y in self.corner.y..max_y {
+            for x in self.corner.x..max_x {
+                visit(x, y);
+            }
+        }
+    }
+}
+
+impl Dot {
+    /// Calls `visit` with every pixel of the canvas inside the dot.
+    fn for_each_pixel<F>(&self, canvas_width: u32, canvas_height: u32, visit: F)
+    where
+        F: FnMut(u32, u32),
+    {
+        let circle = Ellipse {
+            center: self.center.clone(),
+            radii: (self.radius, self.radius),
+            color: self.color,
+        };
+        circle.for_each_pixel(canvas_width, canvas_height, visit);
+    }
+}
+
+/// A pixel as a position with fractions.
+fn position(point: &Point2D) -> (f64, f64) {
+    return (point.x as f64, point.y as f64);
+}
+
+/// The distance between two positions.
+fn between(a: (f64, f64), b: (f64, f64)) -> f64 {
+    return ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+}
+
+/// Moves a point up to 15 pixels in each direction, staying on the canvas.
+fn nudge<R: Rng>(point: &Point2D, rng: &mut R, canvas_width: u32, canvas_height: u32) -> Point2D {
+    let dx = (rng.gen::<u32>() % 31) as f64 - 15.0;
+    let dy = (rng.gen::<u32>() % 31) as f64 - 15.0;
+    return Point2D {
+        x: clamp(point.x as f64 + dx, canvas_width),
+        y: clamp(point.y as f64 + dy, canvas_height),
+    };
+}
+
+/// Grows or shrinks a length by up to a third, keeping it at least one pixel.
+fn resize<R: Rng>(length: u32, rng: &mut R) -> u32 {
+    let scale = 2.0 / 3.0 + rng.gen::<f64>() * 2.0 / 3.0;
+    return ((length as f64 * scale).round() as u32).max(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Polygon;
+    use super::super::point_2d::Point2D;
+    use image;
+    use std::collections::HashSet;
+
+    fn triangle(corners: [(u32, u32); 3]) -> Polygon {
+        return Polygon {
+            corners: corners.iter().map(|&(x, y)| Point2D { x: x, y: y }).collect(),
+            color: image::Rgb { data: [0, 0, 0] },
+        };
+    }
+
+    #[test]
+    fn triangles_sharing_an_edge_tile_without_overlap() {
+        let mut pixels = HashSet::new();
+        let mut count = 0;
+        for half in [[(0, 0), (10, 0), (0, 10)], [(10, 0), (10, 10), (0, 10)]].iter() {
+            triangle(*half).for_each_pixel(20, 20, |x, y| {
+                pixels.insert((x, y));
+                count += 1;
+            });
+        }
+        assert_eq!(count, 100);
+        assert_eq!(pixels.len(), 100);
+        assert!(pixels.iter().all(|&(x, y)| x < 10 && y < 10));
+    }
+
+    #[test]
+    fn triangles_are_clipped_to_the_canvas() {
+        let mut count = 0;
+        triangle([(0, 0), (40, 0), (0, 40)]).for_each_pixel(10, 10, |x, y| {
+            assert!(x < 10 && y < 10);
+            count += 1;
+        });
+        assert_eq!(count, 100);
+    }
+}
@@ -0,0 +1,252 @@
+use image;
+use palette::{Lab, Rgb};
+
+/// Largest number of pixels looked at when clustering, to keep big images quick.
+const MAX_SAMPLES: usize = 20_000;
+
+/// Rounds of refinement k-means goes through.
+const ITERATIONS: u32 = 10;
+
+/// How the dominant colors of an image are found.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Quantizer {
+    /// Clusters the pixels around the given number of centers.
+    KMeans,
+    /// Repeatedly splits the box of colors with the widest range at its median.
+    MedianCut,
+}
+
+impl Quantizer {
+    /// Parses the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Quantizer> {
+        return match name {
+            "kmeans" => Some(Quantizer::KMeans),
+            "mediancut" => Some(Quantizer::MedianCut),
+            _ => None,
+        };
+    }
+}
+
+/// The color space colors are compared in while quantizing.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorSpace {
+    Rgb,
+    /// CIE L*a*b*, where distances follow perceived differences more closely.
+    Lab,
+}
+
+impl ColorSpace {
+    /// Parses the name used on the command line.
+    pub fn from_name(name: &str) -> Option<ColorSpace> {
+        return match name {
+            "rgb" => Some(ColorSpace::Rgb),
+            "lab" => Some(ColorSpace::Lab),
+            _ => None,
+        };
+    }
+}
+
+/// Finds the `count` dominant colors of an image.
+pub fn dominant_colors(
+    image: &image::RgbImage,
+    count: usize,
+    quantizer: Quantizer,
+    space: ColorSpace,
+) -> Vec<image::Rgb<u8>> {
+    let samples: Vec<[f64; 3]> = sample_pixels(image)
+        .iter()
+        .map(|pixel| to_space(pixel, space))
+        .collect();
+    if samples.is_empty() || count == 0 {
+        return Vec::new();
+    }
+    let centers = match quantizer {
+        Quantizer::KMeans => kmeans(&samples, count, space),
+        Quantizer::MedianCut => median_cut(samples, count),
+    };
+    return centers
+        .iter()
+        .map(|center| from_space(center, space))
+        .collect();
+}
+
+/// Clusters the samples around `count` centers.
+fn kmeans(samples: &[[f64; 3]], count: usize, space: ColorSpace) -> Vec<[f64; 3]> {
+    // Start from colors spread evenly over the range of brightness in the image.
+    let brightness = |color: &[f64; 3]| match space {
+        ColorSpace::Rgb => color[0] + color[1] + color[2],
+        ColorSpace::Lab => color[0],
+    };
+    let mut by_brightness = samples.to_vec();
+    by_brightness.sort_by(|a, b| brightness(a).partial_cmp(&brightness(b)).unwrap());
+    let mut centers: Vec<[f64; 3]> = (0..count)
+        .map(|i| by_brightness[(2 * i + 1) * by_brightness.len() / (2 * count)])
+        .collect();
+
+    for _ in 0..ITERATIONS {
+        let mut sums = vec![[0f64; 3]; count];
+        let mut sizes = vec![0usize; count];
+        for sample in samples.iter() {
+            let nearest = nearest_center(&centers, sample);
+            for channel in 0..3 {
+                sums[nearest][channel] += sample[channel];
+            }
+            sizes[nearest] += 1;
+        }
+        for i in 0..count {
+            // Clusters that lost all their pixels keep their last center.
+            if sizes[i] > 0 {
+                for channel in 0..3 {
+                    centers[i][channel] = sums[i][channel] / sizes[i] as f64;
+                }
+            }
+        }
+    }
+    return centers;
+}
+
+/// Splits the samples into `count` boxes, always cutting the box with the widest range of any
+/// channel at its median along that channel, and returns the average of each box.
+fn median_cut(samples: Vec<[f64; 3]>, count: usize) -> Vec<[f64; 3]> {
+    let mut boxes = vec![samples];
+    while boxes.len() < count {
+        let (index, channel, range) = boxes
+            .iter()
+            .enumerate()
+            .map(|(index, colors)| {
+                let (channel, range) = widest_channel(colors);
+                (index, channel, range)
+            })
+            .fold((0, 0, -1.0), |widest, candidate| if candidate.2 > widest.2 {
+                candidate
+            } else {
+                widest
+            });
+        // Every box is a single color already.
+        if range <= 0.0 {
+            break;
+        }
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_by(|a, b| a[channel].partial_cmp(&b[channel]).unwrap());
+        // Cut where the median value starts, or where it ends if it starts the box, so that
+        // pixels of the same color never end up in different boxes.
+        let median = colors[colors.len() / 2][channel];
+        let mut cut = colors.iter().position(|color| color[channel] >= median).unwrap();
+        if cut == 0 {
+            cut = colors.iter().position(|color| color[channel] > median).unwrap();
+        }
+        let upper = colors.split_off(cut);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+    return boxes.iter().map(|colors| average(colors)).collect();
+}
+
+/// The channel with the widest range of values among the colors, and that range.
+fn widest_channel(colors: &[[f64; 3]]) -> (usize, f64) {
+    let mut widest = (0, 0.0);
+    for channel in 0..3 {
+        let min = colors.iter().map(|c| c[channel]).fold(::std::f64::MAX, f64::min);
+        let max = colors.iter().map(|c| c[channel]).fold(::std::f64::MIN, f64::max);
+        if max - min > widest.1 {
+            widest = (channel, max - min);
+        }
+    }
+    return widest;
+}
+
+/// The average of the colors.
+fn average(colors: &[[f64; 3]]) -> [f64; 3] {
+    let mut sum = [0f64; 3];
+    for color in colors {
+        for channel in 0..3 {
+            sum[channel] += color[channel];
+        }
+    }
+    let count = colors.len().max(1) as f64;
+    return [sum[0] / count, sum[1] / count, sum[2] / count];
+}
+
+/// Every pixel of the image, or an evenly spread subset of them for large images.
+fn sample_pixels(image: &image::RgbImage) -> Vec<image::Rgb<u8>> {
+    let total = (image.width() * image.height()) as usize;
+    let step = (total / MAX_SAMPLES).max(1);
+    return image.pixels().step_by(step).cloned().collect();
+}
+
+/// The index of the center closest to the given color.
+fn nearest_center(centers: &[[f64; 3]], color: &[f64; 3]) -> usize {
+    let mut nearest = 0;
+    let mut nearest_distance = ::std::f64::MAX;
+    for (i, center) in centers.iter().enumerate() {
+        let distance = (0..3).map(|c| (center[c] - color[c]).powi(2)).sum::<f64>();
+        if distance < nearest_distance {
+            nearest = i;
+            nearest_distance = distance;
+        }
+    }
+    return nearest;
+}
+
+/// Converts a pixel into the given color space.
+fn to_space(pixel: &image::Rgb<u8>, space: ColorSpace) -> [f64; 3] {
+    let (r, g, b) = (pixel.data[0], pixel.data[1], pixel.data[2]);
+    return match space {
+        ColorSpace::Rgb => [r as f64, g as f64, b as f64],
+        ColorSpace::Lab => {
+            let lab: Lab = Rgb::new(linear(r), linear(g), linear(b)).into();
+            [lab.l as f64, lab.a as f64, lab.b as f64]
+        }
+    };
+}
+
+/// Converts a color back from the given color space into a pixel.
+fn from_space(color: &[f64; 3], space: ColorSpace) -> image::Rgb<u8> {
+    return match space {
+        ColorSpace::Rgb => image::Rgb { data: [to_u8(color[0]), to_u8(color[1]), to_u8(color[2])] },
+        ColorSpace::Lab => {
+            let rgb: Rgb = Lab::new(color[0] as f32, color[1] as f32, color[2] as f32).into();
+            image::Rgb { data: [encoded(rgb.red), encoded(rgb.green), encoded(rgb.blue)] }
+        }
+    };
+}
+
+/// Rounds a channel value into [0, 255].
+fn to_u8(value: f64) -> u8 {
+    return value.round().max(0.0).min(255.0) as u8;
+}
+
+/// Decodes an 8 bit sRGB channel into linear light.
+fn linear(channel: u8) -> f32 {
+    let value = channel as f32 / 255.0;
+    if value <= 0.04045 {
+        return value / 12.92;
+    }
+    return ((value + 0.055) / 1.055).powf(2.4);
+}
+
+/// Encodes a linear light channel into 8 bit sRGB.
+fn encoded(channel: f32) -> u8 {
+    let value = channel.max(0.0).min(1.0);
+    let encoded = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    return to_u8(encoded as f64 * 255.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorSpace, Quantizer, dominant_colors};
+    use image;
+
+    #[test]
+    fn median_cut_finds_both_colors_of_a_two_color_image() {
+        let (red, blue) = (image::Rgb { data: [200, 20, 20] }, image::Rgb { data: [20, 20, 200] });
+        let image = image::ImageBuffer::from_fn(4, 3, |x, _| if x < 1 { red } else { blue });
+        let mut colors = dominant_colors(&image, 2, Quantizer::MedianCut, ColorSpace::Rgb);
+        colors.sort_by_key(|color| color.data);
+        assert_eq!(colors, vec![blue, red]);
+    }
+}
@@ -0,0 +1,131 @@
+use rand::Rng;
+
+/// Smallest and largest a width profile value can be mutated to.
+const MIN_SCALE: f64 = 0.1;
+const MAX_SCALE: f64 = 1.5;
+
+/// How the width of a stroke changes along its length, as three scales of the stroke's width
+/// at its start, middle and end, interpolated by a parabola in between.
+#[derive(Clone, Copy, PartialEq)]
+pub struct WidthProfile {
+    pub start: f64,
+    pub mid: f64,
+    pub end: f64,
+}
+
+impl WidthProfile {
+    /// The same width along the whole stroke.
+    pub fn constant() -> WidthProfile {
+        return WidthProfile {
+            start: 1.0,
+            mid: 1.0,
+            end: 1.0,
+        };
+    }
+
+    /// The scale of the stroke's width at `along` (0 at the start, 1 at the end of the stroke).
+    pub fn at(&self, along: f64) -> f64 {
+        let t = along.max(0.0).min(1.0);
+        let scale = self.start * (2.0 * t - 1.0) * (t - 1.0) + self.mid * 4.0 * t * (1.0 - t) +
+            self.end * t * (2.0 * t - 1.0);
+        return scale.max(0.0);
+    }
+
+    /// The largest scaled `width` anywhere along the stroke, in whole pixels. The profile may
+    /// bulge a little past its largest scale between its control values.
+    pub fn widest(&self, width: u32) -> u32 {
+        const STEPS: u32 = 32;
+        let scale = (0..STEPS + 1)
+            .map(|step| self.at(step as f64 / STEPS as f64))
+            .fold(0.0, f64::max);
+        return (width as f64 * scale).ceil() as u32;
+    }
+
+    /// The stretches of the stroke, as (from, to) pairs of positions along it, where the scaled
+    /// `width` is more than `threshold`.
+    pub fn runs_above(&self, width: u32, threshold: f64) -> Vec<(f64, f64)> {
+        const STEPS: u32 = 16;
+        let mut runs = Vec::new();
+        let mut run_start = None;
+        for step in 0..STEPS {
+            let (from, to) = (step as f64 / STEPS as f64, (step + 1) as f64 / STEPS as f64);
+            let inside = width as f64 * self.at((from + to) / 2.0) > threshold;
+            match (inside, run_start) {
+                (true, None) => run_start = Some(from),
+                (false, Some(start)) => {
+                    runs.push((start, from));
+                    run_start = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(start) = run_start {
+            runs.push((start, 1.0));
+        }
+        return runs;
+    }
+
+    /// Nudges one of the three scales up or down.
+    pub fn mutate<R: Rng>(&self, rng: &mut R) -> WidthProfile {
+        let mut profile = self.clone();
+        let change = rng.gen::<f64>() * 0.5 - 0.25;
+        {
+            let scale = match rng.gen::<u32>() % 3 {
+                0 => &mut profile.start,
+                1 => &mut profile.mid,
+                _ => &mut profile.end,
+            };
+            *scale = (*scale + change).max(MIN_SCALE).min(MAX_SCALE);
+        }
+        return profile;
+    }
+}
+
+/// The width profiles the strokes of a generated painting start with.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ProfileShape {
+    /// The same width along every stroke.
+    Constant,
+    /// Thin at both ends and full width in the middle, like a brush pressed and lifted.
+    Tapered,
+    /// Every stroke gets random start, middle and end widths.
+    Random,
+}
+
+impl ProfileShape {
+    /// Parses the name used on the command line.
+    pub fn from_name(name: &str) -> Option<ProfileShape> {
+        return match name {
+            "constant" => Some(ProfileShape::Constant),
+            "tapered" => Some(ProfileShape::Tapered),
+            "random" => Some(ProfileShape::Random),
+            _ => None,
+        };
+    }
+
+    /// The name used on the command line.
+    pub fn name(&self) -> &'static str {
+        return match *self {
+            ProfileShape::Constant => "constant",
+            ProfileShape::Tapered => "tapered",
+            ProfileShape::Random => "random",
+        };
+    }
+
+    /// Picks the width profile of a new stroke.
+    pub fn pick<R: Rng>(&self, rng: &mut R) -> WidthProfile {
+        return match *self {
+            ProfileShape::Constant => WidthProfile::constant(),
+            ProfileShape::Tapered => WidthProfile {
+                start: 0.3,
+                mid: 1.0,
+                end: 0.3,
+            },
+            ProfileShape::Random => WidthProfile {
+                start: MIN_SCALE + rng.gen::<f64>() * (1.0 - MIN_SCALE),
+                mid: MIN_SCALE + rng.gen::<f64>() * (1.0 - MIN_SCALE),
+                end: MIN_SCALE + rng.gen::<f64>() * (1.0 - MIN_SCALE),
+            },
+        };
+    }
+}
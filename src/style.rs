@@ -0,0 +1,316 @@
+use clap::ArgMatches;
+use std::collections::HashMap;
+use toml;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use monet::RunConfig;
+use monet::stroke::error::{MonetError, Result};
+
+/// Options of the built in presets, by the long name of the command line option they set.
+/// Flags such as `recolor` are set with `true`.
+const IMPRESSIONIST: &'static [(&'static str, &'static str)] = &[
+    ("strokes", "600"),
+    ("strokewidth", "6"),
+    ("minstrokelength", "8"),
+    ("maxstrokelength", "25"),
+    ("generator", "gradient"),
+    ("colorsampling", "mean"),
+    ("placement", "detail"),
+    ("brush", "bristle"),
+    ("widthprofile", "tapered"),
+    ("selector", "tournament"),
+];
+const POINTILLIST: &'static [(&'static str, &'static str)] = &[
+    ("strokes", "3000"),
+    ("strokewidth", "4"),
+    ("generator", "pointillist"),
+    ("colorsampling", "pixel"),
+    ("palette", "auto:12"),
+    ("selector", "tournament"),
+];
+const SKETCH: &'static [(&'static str, &'static str)] = &[
+    ("strokes", "1500"),
+    ("strokewidth", "1"),
+    ("minstrokelength", "10"),
+    ("maxstrokelength", "40"),
+    ("generator", "gradient"),
+    ("placement", "detail"),
+    ("brush", "dry"),
+    ("palette", "black,#808080,white"),
+    ("background", "white"),
+    ("texture", "paper"),
+    ("texturestrength", "0.3"),
+];
+const WATERCOLOUR: &'static [(&'static str, &'static str)] = &[
+    ("strokes", "400"),
+    ("strokewidth", "14"),
+    ("minstrokelength", "10"),
+    ("maxstrokelength", "30"),
+    ("colorsampling", "median"),
+    ("brush", "round"),
+    ("widthprofile", "random"),
+    ("background", "white"),
+    ("mixing", "subtractive"),
+    ("texture", "paper"),
+    ("recolor", "true"),
+];
+
+/// Defaults of the options that only the command line has, where `defaults` doesn't find
+/// them in `RunConfig::default()`.
+const CLI_DEFAULTS: &'static [(&'static str, &'static str)] = &[
+    ("format", "png"),
+    ("jobs", "1"),
+    ("coherence", "50"),
+    ("verbose", "0"),
+    ("quantizer", "kmeans"),
+    ("quantizespace", "rgb"),
+    ("linewidth", "1"),
+];
+
+/// Flags that are short for `generator` set to their own name.
+const GENERATOR_FLAGS: &'static [&'static str] = &["random", "gradient", "pointillist"];
+
+/// A named bundle of command line options giving paintings a particular look. Options given
+/// on the command line win over the style's.
+#[derive(Default)]
+pub struct Style {
+    options: HashMap<String, String>,
+}
+
+impl Style {
+    /// Builds one of the built in styles - impressionist, pointillist, sketch or watercolour -
+    /// or else reads a style file in the format of a config file, setting any of the `known`
+    /// options.
+    pub fn from_spec(spec: &str, known: &[&str]) -> Result<Style> {
+        let preset = match spec {
+            "impressionist" => IMPRESSIONIST,
+            "pointillist" => POINTILLIST,
+            "sketch" => SKETCH,
+            "watercolour" | "watercolor" => WATERCOLOUR,
+            _ if Path::new(spec).is_file() => return Style::load_config(spec, known),
+            _ => {
+                return Err(MonetError::Invalid(
+                    format!("{} is neither a style nor a style file", spec),
+                ))
+            }
+        };
+        return Ok(Style {
+            options: preset
+                .iter()
+                .map(|&(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        });
+    }
+
+    /// The values options take when neither the command line, a config file nor the style give
+    /// them: those of `RunConfig::default()`. The number of strokes and the population have no
+    /// default on the command line.
+    pub fn defaults() -> Style {
+        let run = RunConfig::default();
+        let (painting, postprocess) = (&run.painting, &run.output.postprocess);
+        let mut options: HashMap<String, String> = CLI_DEFAULTS
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        {
+            let mut set = |name: &str, value: String| { options.insert(name.to_string(), value); };
+            set("iterations", run.iterations.to_string());
+            set("selector", run.selection.name().to_string());
+            set("generator", painting.generator.name().to_string());
+            set("strokewidth", painting.stroke_width.to_string());
+            set("minstrokelength", painting.min_length.to_string());
+            set("maxstrokelength", painting.max_length.to_string());
+            set("colorsampling", painting.color_sampling.name().to_string());
+            set("placement", painting.placement.name().to_string());
+            if let Some(background) = painting.background.spec() {
+                set("background", background);
+            }
+            if painting.brushes.len() == 1 {
+                set("brush", painting.brushes[0].name().to_string());
+            }
+            set("widthprofile", painting.profile_shape.name().to_string());
+            let kinds: Vec<&str> = painting.kinds.iter().map(|kind| kind.name()).collect();
+            set("primitives", kinds.join(","));
+            set("mixing", painting.mixing.name().to_string());
+            set("texturestrength", postprocess.texture_strength.to_string());
+            set("impasto", postprocess.impasto.to_string());
+            set("jitter", postprocess.jitter.to_string());
+        }
+        return Style { options: options };
+    }
+
+    /// Reads a TOML config file setting any of the `known` options by their long name, such
+    /// as `strokes = 600` or `brush = "bristle"`, with flags set by `true`. The flags short for
+    /// a generator, such as `gradient = true`, are read as that `generator`.
+    pub fn load_config(path: &str, known: &[&str]) -> Result<Style> {
+        let mut contents = String::new();
+        File::open(Path::new(path))
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|error| MonetError::io(path, error))?;
+        let invalid = |message: String| MonetError::Invalid(format!("{}: {}", path, message));
+        let table = match contents.parse::<toml::Value>() {
+            Ok(toml::Value::Table(table)) => table,
+            Ok(_) => return Err(invalid("not a table of options".to_string())),
+            Err(error) => return Err(invalid(error.to_string())),
+        };
+        let mut options = HashMap::new();
+        for (name, value) in table {
+            if GENERATOR_FLAGS.contains(&name.as_str()) {
+                match value.as_bool() {
+                    Some(true) => options.insert("generator".to_string(), name),
+                    Some(false) => None,
+                    None => return Err(invalid(format!("{} has to be a boolean", name))),
+                };
+                continue;
+            }
+            if !known.contains(&name.as_str()) {
+                return Err(invalid(format!("unknown option {}", name)));
+            }
+            let value = match value {
+                toml::Value::String(value) => value,
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                toml::Value::Boolean(value) => value.to_string(),
+                _ => {
+                    return Err(invalid(format!("{} has to be a string, number or boolean", name)))
+                }
+            };
+            options.insert(name, value);
+        }
+        return Ok(Style { options: options });
+    }
+
+    /// Layers these options over `base`, winning where both set an option.
+    pub fn over(self, base: Style) -> Style {
+        let mut options = base.options;
+        options.extend(self.options);
+        return Style { options: options };
+    }
+
+    /// Describes the value every one of `names` takes, as a config file `load_config` reads
+    /// back. Options without a value and flags that aren't set are left out.
+    pub fn dump(&self, args: &ArgMatches, names: &[&str]) -> String {
+        let mut config = String::new();
+        for name in names {
+            let value = match self.value_of(args, name) {
+                // A flag on the command line wins over its value in a config file or style.
+                _ if args.is_present(name) && args.value_of(name).is_none() => "true".to_string(),
+                Some(value) if is_toml_literal(value) => value.to_string(),
+                Some(value) => {
+                    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+                }
+                None => continue,
+            };
+            config.push_str(&format!("{} = {}\n", name, value));
+        }
+        return config;
+    }
+
+    /// The value of an option, from the command line if given there, else from the style. A
+    /// generator flag on the command line gives the `generator`.
+    pub fn value_of<'a>(&'a self, args: &'a ArgMatches, name: &str) -> Option<&'a str> {
+        if name == "generator" {
+            if let Some(flag) = GENERATOR_FLAGS.iter().find(|flag| args.is_present(flag)) {
+                return Some(flag);
+            }
+        }
+        return args.value_of(name)
+            .or_else(|| self.options.get(name).map(|value| value.as_str()));
+    }
+
+    /// Parses the value of an option, which has to be given.
+    pub fn parse<T: FromStr>(&self, args: &ArgMatches, name: &str) -> Result<T> {
+        let value = self.required(args, name)?;
+        return value
+            .parse()
+            .map_err(|_| MonetError::Invalid(format!("invalid value for {}: {}", name, value)));
+    }
+
+    /// Parses the value of an option naming one of several choices with `from_name`, such as
+    /// `Placement::from_name`. It has to be given.
+    pub fn choice<T, F>(&self, args: &ArgMatches, name: &str, from_name: F) -> Result<T>
+    where
+        F: Fn(&str) -> Option<T>,
+    {
+        let value = self.required(args, name)?;
+        return from_name(value)
+            .ok_or_else(|| MonetError::Invalid(format!("invalid value for {}: {}", name, value)));
+    }
+
+    /// The value of an option that has to be given.
+    pub fn required<'a>(&'a self, args: &'a ArgMatches, name: &str) -> Result<&'a str> {
+        return self.value_of(args, name).ok_or_else(|| {
+            MonetError::Invalid(format!(
+                "--{} is required, on the command line, in a config file or by the style",
+                name
+            ))
+        });
+    }
+
+    /// Whether a flag is set, on the command line or by the style.
+    pub fn is_present(&self, args: &ArgMatches, name: &str) -> bool {
+        return args.is_present(name) ||
+            self.options.get(name).map_or(false, |value| value == "true");
+    }
+}
+
+/// Whether a value can be written to a config file as is, being a number or a boolean.
+fn is_toml_literal(value: &str) -> bool {
+    if value == "true" || value == "false" {
+        return true;
+    }
+    let is_digits = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+    let mut parts = value.trim_start_matches('-').splitn(2, '.');
+    let whole = parts.next().unwrap_or("");
+    let fraction_is_valid = match parts.next() {
+        Some(fraction) => is_digits(fraction),
+        None => true,
+    };
+    // TOML doesn't allow leading zeros.
+    return is_digits(whole) && fraction_is_valid && (whole == "0" || !whole.starts_with('0'));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Style, is_toml_literal};
+    use clap::{App, Arg};
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::process;
+
+    const NAMES: &'static [&'static str] = &[
+        "strokes", "generator", "brush", "texture", "recolor", "texturestrength", "minstrokelength",
+    ];
+
+    #[test]
+    fn dumped_configs_load_back() {
+        let args = App::new("test")
+            .arg(Arg::with_name("gradient").long("gradient"))
+            .get_matches_from(vec!["test", "--gradient"]);
+        let mut style = Style::from_spec("watercolour", NAMES).unwrap().over(Style::defaults());
+        style.options.insert("texture".to_string(), "C:\\paper \"rough\".png".to_string());
+        let dumped = style.dump(&args, NAMES);
+        assert!(dumped.contains("generator = \"gradient\"\n"));
+        assert!(dumped.contains("recolor = true\n"));
+
+        let path = env::temp_dir().join(format!("monet-style-{}.toml", process::id()));
+        File::create(&path).unwrap().write_all(dumped.as_bytes()).unwrap();
+        let loaded = Style::load_config(path.to_str().unwrap(), NAMES);
+        fs::remove_file(&path).unwrap();
+        let no_args = App::new("test").get_matches_from(vec!["test"]);
+        assert_eq!(loaded.unwrap().dump(&no_args, NAMES), dumped);
+    }
+
+    #[test]
+    fn only_numbers_and_booleans_are_literals() {
+        for value in &["5", "0", "-3", "0.5", "true", "false"] {
+            assert!(is_toml_literal(value), "{}", value);
+        }
+        for value in &["", "007", "1e5", "5.", ".5", "white", "#ffffff", "True"] {
+            assert!(!is_toml_literal(value), "{}", value);
+        }
+    }
+}